@@ -1,6 +1,8 @@
+use crate::config::config;
 use crate::fsrs::sqlite_history::SQLiteHistory;
 use crate::utils::create_sub_dir;
 use crate::utils::rating_from_u8;
+use crate::view;
 use crate::{query, spaced_repetition::SpacedRepetition};
 use anyhow::Result;
 use axum::extract::State;
@@ -14,7 +16,6 @@ use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::fs::File;
 use std::io::Write;
-use std::process::Command;
 use std::sync::mpsc::channel;
 use std::sync::Arc;
 use std::thread;
@@ -212,10 +213,9 @@ pub async fn anki() -> Result<()> {
 
     let _server_thread = thread::spawn(move || {
         let _ = receiver.recv().unwrap();
-        let _ = Command::new("carbonyl")
-            .arg("http://127.0.0.1:3333")
-            .status()
-            .unwrap();
+        if let Err(e) = view::open(&format!("http://{}", config().server.bind)) {
+            error!("{e}");
+        }
     });
 
     let app_state = Arc::new(AppState {
@@ -271,11 +271,11 @@ pub async fn anki() -> Result<()> {
         .route("/ppppp", post(handler))
         .with_state(app_state)
         .layer(TraceLayer::new_for_http());
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:3333")
+    let listener = tokio::net::TcpListener::bind(&config().server.bind)
         .await
         .unwrap();
     sender.send(1).unwrap();
-    println!("open http://127.0.0.1:3333");
+    println!("open http://{}", config().server.bind);
     axum::serve(listener, app).await.unwrap();
 
     Ok(())
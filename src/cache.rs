@@ -0,0 +1,359 @@
+//! Caches under `~/.cache/mdict-cli-rs`, or `cache_dir` of the config, so a lookup doesn't
+//! parse every dictionary:
+//! - `catalog.json`, the names of the dictionaries, to list them without opening them
+//! - `normalized/`, fst maps from the normalized headwords of a dictionary, memory mapped
+//! - `pages/`, where the pages of a StarDict idx start, so it isn't read through on start
+//!
+//! An entry is made again when the file it was made from changes.
+use crate::config::{self, config};
+use crate::headword_index::Source;
+use crate::normalize::{normalize, steps_setting};
+use anyhow::{Context, Result};
+use fst::{Map, MapBuilder};
+use log::*;
+use memmap2::Mmap;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+pub fn cache_dir() -> PathBuf {
+    match &config().cache_dir {
+        Some(path) => config::expand(path),
+        None => dirs::cache_dir().unwrap().join("mdict-cli-rs"),
+    }
+}
+
+/// write `path` through a temporary file, so a mapped file is never changed in place
+pub fn replace(path: &Path, write: impl FnOnce(File) -> Result<()>) -> Result<()> {
+    let dir = path.parent().unwrap();
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create directory {:?}", dir))?;
+    let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+    let file = File::create(&tmp).with_context(|| format!("fail to create {:?}", tmp))?;
+    if let Err(e) = write(file) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    fs::rename(&tmp, path).with_context(|| format!("Failed to write {:?}", path))?;
+    Ok(())
+}
+
+/// the name of the cache files of the dictionary at `path`, the same with every build
+fn stem(path: &Path) -> String {
+    // FNV-1a, `DefaultHasher` may change between Rust releases
+    let hash = path
+        .as_os_str()
+        .as_encoded_bytes()
+        .iter()
+        .fold(0xcbf29ce484222325u64, |h, &b| {
+            (h ^ b as u64).wrapping_mul(0x100000001b3)
+        });
+    format!(
+        "{}-{hash:016x}",
+        path.file_stem().unwrap_or_default().to_string_lossy()
+    )
+}
+
+/// whether the cache was made as `manifest` says, by the manifest at `path` written with it
+fn is_fresh<M: PartialEq + DeserializeOwned>(path: &Path, manifest: &M) -> bool {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|s| serde_json::from_str::<M>(&s).ok())
+        .is_some_and(|old| old == *manifest)
+}
+
+#[derive(Serialize, Deserialize)]
+struct CatalogEntry {
+    source: Source,
+    name: String,
+}
+
+/// the names of the dictionaries, by the file they are read from
+pub struct Catalog {
+    entries: HashMap<PathBuf, CatalogEntry>,
+    /// the entries of the dictionaries found this time
+    seen: HashMap<PathBuf, CatalogEntry>,
+    changed: bool,
+}
+
+impl Catalog {
+    pub fn open() -> Catalog {
+        let path = cache_dir().join("catalog.json");
+        let entries: Vec<CatalogEntry> = fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        Catalog {
+            entries: entries
+                .into_iter()
+                .map(|e| (e.source.path.clone(), e))
+                .collect(),
+            seen: HashMap::new(),
+            changed: false,
+        }
+    }
+
+    /// the name of the dictionary read from `path`, `name` makes it when `path` changed
+    pub fn name(&mut self, path: &Path, name: impl FnOnce() -> Result<String>) -> Result<String> {
+        let source = Source::new(path);
+        let entry = match self.entries.remove(path) {
+            Some(entry) if entry.source == source => entry,
+            _ => {
+                self.changed = true;
+                CatalogEntry {
+                    source,
+                    name: name()?,
+                }
+            }
+        };
+        let name = entry.name.clone();
+        self.seen.insert(path.to_path_buf(), entry);
+        Ok(name)
+    }
+
+    /// keep the entries of the dictionaries found, if any changed
+    pub fn save(self) {
+        if !self.changed && self.entries.is_empty() {
+            return;
+        }
+        let path = cache_dir().join("catalog.json");
+        let mut entries: Vec<CatalogEntry> = self.seen.into_values().collect();
+        entries.sort_by(|a, b| a.source.path.cmp(&b.source.path));
+        let res = replace(&path, |file| {
+            Ok(serde_json::to_writer_pretty(file, &entries)?)
+        });
+        if let Err(e) = res {
+            error!("{e:?}");
+        }
+    }
+}
+
+#[derive(PartialEq, Serialize, Deserialize)]
+struct Manifest {
+    source: Source,
+    /// the steps the headwords were normalized with
+    normalize: String,
+}
+
+/// the map from the normalized headwords of the dictionary read from `path`,
+/// `headwords` lists (headword, value) when it has to be made,
+/// the first value is kept when several headwords are normalized the same
+pub fn normalized(
+    path: &Path,
+    headwords: impl FnOnce() -> Result<Vec<(String, u64)>>,
+) -> Result<Map<Mmap>> {
+    normalized_in(&cache_dir(), path, headwords)
+}
+
+fn normalized_in(
+    cache_dir: &Path,
+    path: &Path,
+    headwords: impl FnOnce() -> Result<Vec<(String, u64)>>,
+) -> Result<Map<Mmap>> {
+    let stem = stem(path);
+    let dir = cache_dir.join("normalized");
+    let fst_path = dir.join(format!("{stem}.fst"));
+    let manifest_path = dir.join(format!("{stem}.json"));
+
+    let manifest = Manifest {
+        source: Source::new(path),
+        normalize: steps_setting(),
+    };
+    if !(is_fresh(&manifest_path, &manifest) && fst_path.exists()) {
+        info!("caching the headwords of {:?}", path);
+        let mut words: Vec<(String, u64)> = headwords()?
+            .into_iter()
+            .map(|(w, value)| (normalize(&w), value))
+            .collect();
+        // stable, the first of the same words stays first
+        words.sort_by(|a, b| a.0.cmp(&b.0));
+        words.dedup_by(|a, b| a.0 == b.0);
+        replace(&fst_path, |file| {
+            let mut builder = MapBuilder::new(BufWriter::new(file))?;
+            for (w, value) in words {
+                builder.insert(w, value)?;
+            }
+            builder.finish()?;
+            Ok(())
+        })?;
+        replace(&manifest_path, |file| {
+            Ok(serde_json::to_writer_pretty(file, &manifest)?)
+        })?;
+    }
+
+    let file = File::open(&fst_path).with_context(|| format!("Failed to open {:?}", fst_path))?;
+    // the cache is replaced by a rename, never written in place
+    let mmap =
+        unsafe { Mmap::map(&file) }.with_context(|| format!("Failed to map {:?}", fst_path))?;
+    Ok(Map::new(mmap)?)
+}
+
+/// where the pages of the StarDict idx at `path` start, `pages` finds them when `path` changed
+pub fn pages(path: &Path, pages: impl FnOnce() -> Result<Vec<usize>>) -> Result<Vec<usize>> {
+    pages_in(&cache_dir(), path, pages)
+}
+
+fn pages_in(
+    cache_dir: &Path,
+    path: &Path,
+    pages: impl FnOnce() -> Result<Vec<usize>>,
+) -> Result<Vec<usize>> {
+    let stem = stem(path);
+    let dir = cache_dir.join("pages");
+    let pages_path = dir.join(format!("{stem}.bin"));
+    let manifest_path = dir.join(format!("{stem}.json"));
+
+    let source = Source::new(path);
+    if is_fresh(&manifest_path, &source) {
+        if let Ok(bytes) = fs::read(&pages_path) {
+            return Ok(bytes
+                .chunks_exact(8)
+                .map(|b| u64::from_le_bytes(b.try_into().unwrap()) as usize)
+                .collect());
+        }
+    }
+
+    info!("caching the pages of {:?}", path);
+    let pages = pages()?;
+    let res = replace(&pages_path, |mut file| {
+        let bytes: Vec<u8> = pages
+            .iter()
+            .flat_map(|&pos| (pos as u64).to_le_bytes())
+            .collect();
+        Ok(file.write_all(&bytes)?)
+    })
+    .and_then(|()| {
+        replace(&manifest_path, |file| {
+            Ok(serde_json::to_writer_pretty(file, &source)?)
+        })
+    });
+    // the idx can still be read without the cache
+    if let Err(e) = res {
+        error!("{e:?}");
+    }
+    Ok(pages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use std::cell::Cell;
+
+    #[test]
+    fn stems() {
+        let a = stem(Path::new("/dicts/oald.mdx"));
+        assert_eq!(a, stem(Path::new("/dicts/oald.mdx")));
+        // FNV-1a of the path, it must not change between builds
+        assert_eq!(a, "oald-4b308426051a8ab1");
+        assert_ne!(a, stem(Path::new("/other/oald.mdx")));
+        assert!(stem(Path::new("/dicts/oald.mdd")).starts_with("oald-"));
+        assert_ne!(a, stem(Path::new("/dicts/oald.mdd")));
+    }
+
+    #[test]
+    fn replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sub").join("a.json");
+        replace(&path, |mut file| Ok(file.write_all(b"one")?)).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"one");
+        replace(&path, |mut file| Ok(file.write_all(b"two")?)).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"two");
+
+        // a failed write leaves the old file
+        let e = replace(&path, |mut file| {
+            file.write_all(b"thr")?;
+            Err(anyhow!("full"))
+        })
+        .unwrap_err();
+        assert_eq!(e.to_string(), "full");
+        assert_eq!(fs::read(&path).unwrap(), b"two");
+        // and no temporary file
+        let names: Vec<_> = fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["a.json"]);
+    }
+
+    #[test]
+    fn fresh() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("m.json");
+        assert!(!is_fresh(&path, &1));
+        fs::write(&path, "1").unwrap();
+        assert!(is_fresh(&path, &1));
+        assert!(!is_fresh(&path, &2));
+        fs::write(&path, "not json").unwrap();
+        assert!(!is_fresh(&path, &1));
+    }
+
+    #[test]
+    fn pages_invalidated() {
+        let dir = tempfile::tempdir().unwrap();
+        let idx = dir.path().join("a.idx");
+        fs::write(&idx, "a").unwrap();
+        let made = Cell::new(0);
+        let get = || {
+            pages_in(dir.path(), &idx, || {
+                made.set(made.get() + 1);
+                Ok(vec![0, 7, 1 << 40])
+            })
+            .unwrap()
+        };
+        assert_eq!(get(), [0, 7, 1 << 40]);
+        assert_eq!(get(), [0, 7, 1 << 40]);
+        assert_eq!(made.get(), 1);
+
+        fs::write(&idx, "ab").unwrap();
+        assert_eq!(get(), [0, 7, 1 << 40]);
+        assert_eq!(made.get(), 2);
+
+        // a lost cache file is made again
+        let pages_dir = dir.path().join("pages");
+        fs::remove_file(pages_dir.join(format!("{}.bin", stem(&idx)))).unwrap();
+        get();
+        assert_eq!(made.get(), 3);
+    }
+
+    #[test]
+    fn normalized_invalidated() {
+        let dir = tempfile::tempdir().unwrap();
+        let dict = dir.path().join("a.mdx");
+        fs::write(&dict, "a").unwrap();
+        let made = Cell::new(0);
+        let get = || {
+            normalized_in(dir.path(), &dict, || {
+                made.set(made.get() + 1);
+                Ok(vec![("Apple".to_owned(), 1), ("apple".to_owned(), 2)])
+            })
+            .unwrap()
+        };
+        let map = get();
+        // the first of the headwords normalized the same
+        assert_eq!(map.get(normalize("apple")), Some(1));
+        get();
+        assert_eq!(made.get(), 1);
+
+        fs::write(&dict, "ab").unwrap();
+        get();
+        assert_eq!(made.get(), 2);
+
+        // normalized with other steps
+        let manifest_path = dir
+            .path()
+            .join("normalized")
+            .join(format!("{}.json", stem(&dict)));
+        let manifest = Manifest {
+            source: Source::new(&dict),
+            normalize: "none".to_owned(),
+        };
+        fs::write(&manifest_path, serde_json::to_string(&manifest).unwrap()).unwrap();
+        get();
+        assert_eq!(made.get(), 3);
+        get();
+        assert_eq!(made.get(), 3);
+    }
+}
@@ -0,0 +1,176 @@
+//! Settings from `~/.config/mdict-cli-rs/config.toml`, or the file given by `--config`,
+//! all optional.
+//!
+//! ```toml
+//! # searched recursively, the first one also keeps the indexes
+//! dictionary_dirs = ["~/.local/share/mdict-cli-rs", "/usr/share/stardict/dic"]
+//! db_path = "~/.local/share/mdict-cli-rs/history.db"
+//! log_dir = "~/.cache/mdict-cli-rs"
+//! # the catalog of dictionaries and their cached indexes
+//! cache_dir = "~/.cache/mdict-cli-rs"
+//! # the command showing a result page, see `view::open`
+//! viewer = "w3m {path}"
+//! # the steps of `normalize::normalize`
+//! normalize = "nfkc,lowercase,diacritics,punctuation,strip"
+//! # dictionaries by name or file name, these ones first and in this order
+//! order = ["WordNet", "fruits"]
+//! # dictionaries by name or file name which are not loaded
+//! disabled = ["wikipedia_en_all.zim"]
+//!
+//! [server]
+//! # `--serve` and `anki`
+//! bind = "127.0.0.1:3333"
+//! # `--serve-dict`
+//! dict_bind = "127.0.0.1:2628"
+//!
+//! # by file name or name, also set by `mdict-cli-rs --dicts`
+//! [dictionaries."oald.mdx"]
+//! # higher first, before `order`, 0 by default
+//! priority = 10
+//! enabled = true
+//! # shown instead of the name
+//! alias = "OALD"
+//! # the result shown when the page opens
+//! open = true
+//!
+//! # chosen by `--group`, a folder of a dictionary dir is a group too
+//! [groups]
+//! # the dictionaries in these folders
+//! en-zh = { dirs = ["~/.local/share/mdict-cli-rs/en-zh"] }
+//! # by file name or name
+//! ja = { dictionaries = ["JaEn", "daijirin.mdx"] }
+//! ```
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub dictionary_dirs: Vec<PathBuf>,
+    pub db_path: Option<PathBuf>,
+    pub log_dir: Option<PathBuf>,
+    pub cache_dir: Option<PathBuf>,
+    /// the command showing a result page, overridden by `MDICT_CLI_VIEWER`
+    pub viewer: Option<String>,
+    /// overridden by `MDICT_CLI_NORMALIZE`
+    pub normalize: Option<String>,
+    pub order: Vec<String>,
+    pub disabled: Vec<String>,
+    pub server: Server,
+    pub dictionaries: BTreeMap<String, DictSettings>,
+    pub groups: BTreeMap<String, Group>,
+}
+
+impl Config {
+    /// the settings of the dictionary called `name` at `path`
+    pub fn dictionary(&self, name: &str, path: &Path) -> DictSettings {
+        self.dictionaries
+            .iter()
+            .find(|(key, _)| is_named(name, path, key))
+            .map(|(_, settings)| settings.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DictSettings {
+    /// higher first
+    pub priority: i64,
+    pub enabled: bool,
+    /// shown instead of the name
+    pub alias: Option<String>,
+    /// the result shown when the page opens
+    pub open: bool,
+}
+
+impl Default for DictSettings {
+    fn default() -> DictSettings {
+        DictSettings {
+            priority: 0,
+            enabled: true,
+            alias: None,
+            open: false,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Server {
+    pub bind: String,
+    pub dict_bind: String,
+}
+
+impl Default for Server {
+    fn default() -> Server {
+        Server {
+            bind: "127.0.0.1:3333".to_owned(),
+            dict_bind: "127.0.0.1:2628".to_owned(),
+        }
+    }
+}
+
+/// the dictionaries in `dirs` and those in `dictionaries`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Group {
+    pub dirs: Vec<PathBuf>,
+    /// by file name or name
+    pub dictionaries: Vec<String>,
+}
+
+static PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// read the config from `path`, before it's used
+pub fn set_config_path(path: PathBuf) {
+    let _ = PATH.set(path);
+}
+
+pub fn config_path() -> PathBuf {
+    PATH.get().cloned().unwrap_or_else(|| {
+        dirs::config_dir()
+            .unwrap()
+            .join("mdict-cli-rs")
+            .join("config.toml")
+    })
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// read the config, before it's used, the defaults if the file doesn't exist
+pub fn load() -> Result<()> {
+    let path = config_path();
+    let config = match fs::read_to_string(&path) {
+        Ok(s) => toml::from_str(&s).with_context(|| format!("Failed to parse {:?}", path))?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Config::default(),
+        Err(e) => return Err(e).with_context(|| format!("Failed to open {:?}", path)),
+    };
+    let _ = CONFIG.set(config);
+    Ok(())
+}
+
+/// the config read by `load`
+pub fn config() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+/// whether `key` is the name, the file name or the file stem of the dictionary at `path`
+pub fn is_named(name: &str, path: &Path, key: &str) -> bool {
+    name == key
+        || path.file_name().is_some_and(|n| n == key)
+        || path.file_stem().is_some_and(|n| n == key)
+}
+
+/// `path` with a leading `~` replaced by the home dir
+pub fn expand(path: &Path) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(rest) => dirs::home_dir().unwrap().join(rest),
+        Err(_) => path.to_path_buf(),
+    }
+}
@@ -0,0 +1,403 @@
+//! Look up words from a dictd server (RFC 2229).
+//!
+//! `foo.dictd` in the dictionary dir declares the server:
+//! `{"name": "WordNet", "host": "localhost", "port": 2628, "databases": ["wn"]}`
+use crate::normalize::normalize;
+use crate::utils::create_sub_dir;
+use crate::utils::escape_html;
+use crate::utils::groom_name;
+use crate::T;
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use std::fs::{self, File};
+use std::io::{prelude::*, BufReader};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Deserialize)]
+struct Config {
+    name: Option<String>,
+    #[serde(default = "default_host")]
+    host: String,
+    #[serde(default = "default_port")]
+    port: u16,
+    /// empty means all databases
+    #[serde(default)]
+    databases: Vec<String>,
+}
+
+fn default_host() -> String {
+    "localhost".to_owned()
+}
+
+fn default_port() -> u16 {
+    2628
+}
+
+pub struct DictClient {
+    config_path: PathBuf,
+    name: String,
+    config: Config,
+}
+
+/// A definition returned by `DEFINE`.
+pub struct Definition {
+    pub database: String,
+    pub description: String,
+    pub text: String,
+}
+
+/// One connection to the server.
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Connection {
+    fn open(host: &str, port: u16) -> Result<Connection> {
+        let addrs = (host, port)
+            .to_socket_addrs()
+            .with_context(|| format!("Failed to resolve dict://{host}:{port}"))?;
+        // an unreachable server would stall every lookup for minutes
+        let mut stream = Err(anyhow!("no address for dict://{host}:{port}"));
+        for addr in addrs {
+            stream = TcpStream::connect_timeout(&addr, TIMEOUT)
+                .with_context(|| format!("Failed to connect dict://{host}:{port}"));
+            if stream.is_ok() {
+                break;
+            }
+        }
+        let stream = stream?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        let mut conn = Connection {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        };
+        let (code, line) = conn.status()?;
+        if code != 220 {
+            return Err(anyhow!("dict://{host}:{port} refused: {line}"));
+        }
+        Ok(conn)
+    }
+
+    fn command(&mut self, cmd: &str) -> Result<(u16, String)> {
+        write!(self.writer, "{cmd}\r\n")?;
+        self.writer.flush()?;
+        self.status()
+    }
+
+    fn line(&mut self) -> Result<String> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(anyhow!("dict server closed the connection"));
+        }
+        Ok(line.trim_end_matches(['\r', '\n']).to_owned())
+    }
+
+    fn status(&mut self) -> Result<(u16, String)> {
+        let line = self.line()?;
+        let code = line
+            .get(..3)
+            .and_then(|c| c.parse().ok())
+            .with_context(|| format!("bad dict response: {line}"))?;
+        Ok((code, line))
+    }
+
+    /// read a text block terminated by a single "."
+    fn text(&mut self) -> Result<String> {
+        let mut text = String::new();
+        loop {
+            let line = self.line()?;
+            if line == "." {
+                return Ok(text);
+            }
+            text.push_str(line.strip_prefix('.').unwrap_or(&line));
+            text.push('\n');
+        }
+    }
+
+    /// `DEFINE` `word` in `db`, nothing when it isn't there
+    fn define(&mut self, db: &str, word: &str) -> Result<Vec<Definition>> {
+        let (code, line) = self.command(&format!("DEFINE {db} {}", quote(word)))?;
+        let mut v = Vec::new();
+        match code {
+            150 => loop {
+                let (code, line) = self.status()?;
+                if code != 151 {
+                    break;
+                }
+                // 151 "word" database "description"
+                let parts = split_quoted(&line);
+                let text = self.text()?;
+                v.push(Definition {
+                    database: parts.get(2).cloned().unwrap_or_default(),
+                    description: parts.get(3).cloned().unwrap_or_default(),
+                    text,
+                });
+            },
+            552 => {}
+            _ => return Err(anyhow!("DEFINE failed: {line}")),
+        }
+        Ok(v)
+    }
+
+    fn quit(mut self) {
+        let _ = self.command("QUIT");
+    }
+}
+
+/// quote a word or database name
+pub fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// split a response line into words, honouring quotes
+pub fn split_quoted(line: &str) -> Vec<String> {
+    let mut v = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let mut word = String::new();
+        if c == '"' || c == '\'' {
+            chars.next();
+            while let Some(x) = chars.next() {
+                match x {
+                    '\\' => word.extend(chars.next()),
+                    x if x == c => break,
+                    x => word.push(x),
+                }
+            }
+        } else {
+            while let Some(&x) = chars.peek() {
+                if x.is_whitespace() {
+                    break;
+                }
+                word.push(x);
+                chars.next();
+            }
+        }
+        v.push(word);
+    }
+    v
+}
+
+impl DictClient {
+    pub fn new(config_path: &Path) -> Result<DictClient> {
+        let s = fs::read_to_string(config_path)
+            .with_context(|| format!("Failed to open dictd config {:?}", config_path))?;
+        let config: Config = serde_json::from_str(&s)
+            .with_context(|| format!("Failed to parse dictd config {:?}", config_path))?;
+        let name = match &config.name {
+            Some(name) => name.clone(),
+            None => config_path
+                .file_stem()
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_owned(),
+        };
+        Ok(DictClient {
+            config_path: config_path.to_path_buf(),
+            name,
+            config,
+        })
+    }
+
+    fn databases(&self) -> Vec<&str> {
+        if self.config.databases.is_empty() {
+            vec!["*"]
+        } else {
+            self.config.databases.iter().map(String::as_str).collect()
+        }
+    }
+
+    /// `MATCH` in every configured database, return (database, word)
+    pub fn match_words(&self, word: &str, strategy: &str) -> Result<Vec<(String, String)>> {
+        let mut conn = Connection::open(&self.config.host, self.config.port)?;
+        let mut v = Vec::new();
+        for db in self.databases() {
+            let (code, line) = conn.command(&format!("MATCH {db} {strategy} {}", quote(word)))?;
+            match code {
+                152 => {
+                    for m in conn.text()?.lines() {
+                        if let [db, word] = &split_quoted(m)[..] {
+                            v.push((db.clone(), word.clone()));
+                        }
+                    }
+                    conn.status()?;
+                }
+                552 => {}
+                _ => return Err(anyhow!("MATCH failed: {line}")),
+            }
+        }
+        conn.quit();
+        Ok(v)
+    }
+
+    /// `DEFINE` `word` in every configured database, then its normalized form
+    /// if it isn't found, over one connection
+    pub fn define(&self, word: &str) -> Result<Vec<Definition>> {
+        let mut conn = Connection::open(&self.config.host, self.config.port)?;
+        let mut v = Vec::new();
+        let normalized = normalize(word);
+        for word in [word, &normalized] {
+            for db in self.databases() {
+                v.extend(conn.define(db, word)?);
+            }
+            if !v.is_empty() || normalized == word {
+                break;
+            }
+        }
+        conn.quit();
+        Ok(v)
+    }
+}
+
+fn to_html(word: &str, definitions: &[Definition]) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"UTF-8\"><title>{}</title></head><body>\n",
+        escape_html(word)
+    );
+    for d in definitions {
+        // {word} are cross references
+        let text = escape_html(&d.text)
+            .replace('{', "<b>")
+            .replace('}', "</b>");
+        html.push_str(&format!(
+            "<h3>{}</h3>\n<pre style=\"white-space: pre-wrap\">{text}</pre>\n",
+            escape_html(if d.description.is_empty() {
+                &d.database
+            } else {
+                &d.description
+            }),
+        ));
+    }
+    html.push_str("</body></html>\n");
+    html
+}
+
+impl T for DictClient {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn path(&self) -> &Path {
+        &self.config_path
+    }
+
+    fn definition(&self, word: &str) -> Result<String> {
+        let definitions = self.define(word)?;
+        if definitions.is_empty() {
+            return Result::Err(anyhow!("not found"));
+        }
+        Ok(to_html(word, &definitions))
+    }
+
+    fn suggestions(&self, word: &str) -> Result<Vec<String>> {
+        let words = self.match_words(word, "lev")?;
+        Ok(words.into_iter().map(|(_, w)| w).collect())
+    }
+
+    fn lookup(&self, word: &str, base_dir: &Path) -> Result<PathBuf> {
+        let html = self.definition(word)?;
+
+        let base_dir = create_sub_dir(base_dir, &groom_name(&self.name))?;
+        let index_html = base_dir.join("index.html");
+        File::create(&index_html)?.write_all(html.as_bytes())?;
+        Ok(base_dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn quoting() {
+        assert_eq!(quote(r#"say "hi" \o/"#), r#""say \"hi\" \\o/""#);
+        assert_eq!(
+            split_quoted(r#"151 "ice cream" wn "WordNet (r) 3.0""#),
+            ["151", "ice cream", "wn", "WordNet (r) 3.0"]
+        );
+        assert_eq!(split_quoted(r#"wn 'it\'s'"#), ["wn", "it's"]);
+        assert_eq!(split_quoted(&quote(r#"a "b" \c"#)), [r#"a "b" \c"#]);
+        assert!(split_quoted("  ").is_empty());
+    }
+
+    #[test]
+    fn html() {
+        let d = Definition {
+            database: "wn".to_owned(),
+            description: String::new(),
+            text: "see {apple} <b>".to_owned(),
+        };
+        let html = to_html("a&b", &[d]);
+        assert!(html.contains("<title>a&amp;b</title>"));
+        assert!(html.contains("<h3>wn</h3>"));
+        assert!(html.contains("see <b>apple</b> &lt;b&gt;"));
+    }
+
+    /// a server which knows "apple" only, over a single connection
+    fn serve(listener: TcpListener) -> Vec<String> {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = stream;
+        let mut commands = Vec::new();
+        writer.write_all(b"220 test <auth> <msg@id>\r\n").unwrap();
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 0 {
+            let cmd = line.trim_end().to_owned();
+            line.clear();
+            let reply = if cmd == "DEFINE wn \"apple\"" {
+                "150 1 definitions retrieved\r\n\
+                 151 \"apple\" wn \"WordNet\"\r\n\
+                 fruit\r\n\
+                 ..dot\r\n\
+                 .\r\n\
+                 250 ok\r\n"
+            } else if cmd == "QUIT" {
+                "221 bye\r\n"
+            } else {
+                "552 no match\r\n"
+            };
+            writer.write_all(reply.as_bytes()).unwrap();
+            commands.push(cmd);
+        }
+        commands
+    }
+
+    #[test]
+    fn define() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || serve(listener));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wn.dictd");
+        let config = format!(r#"{{"host": "127.0.0.1", "port": {port}, "databases": ["wn"]}}"#);
+        fs::write(&path, config).unwrap();
+        let client = DictClient::new(&path).unwrap();
+        assert_eq!(client.name, "wn");
+
+        let v = client.define("Apple").unwrap();
+        assert_eq!(v.len(), 1);
+        assert_eq!(
+            (v[0].database.as_str(), v[0].description.as_str()),
+            ("wn", "WordNet")
+        );
+        assert_eq!(v[0].text, "fruit\n.dot\n");
+        assert_eq!(
+            server.join().unwrap(),
+            ["DEFINE wn \"Apple\"", "DEFINE wn \"apple\"", "QUIT"]
+        );
+    }
+}
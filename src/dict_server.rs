@@ -0,0 +1,276 @@
+//! Serve all dictionaries over the DICT protocol (RFC 2229).
+use crate::dict_client::{quote, split_quoted};
+use crate::headword_index::HeadwordIndex;
+use crate::library::{load_library, Database, Library};
+use crate::render::html_to_text;
+use anyhow::Result;
+use log::*;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+const STRATEGIES: [(&str, &str); 3] = [
+    ("exact", "Match headwords exactly"),
+    ("prefix", "Match prefixes"),
+    ("lev", "Match headwords within Levenshtein distance one"),
+];
+
+impl Database {
+    fn define(&self, word: &str) -> Option<String> {
+        let html = self.dict.lock().unwrap().definition(word).ok()?;
+        Some(html_to_text(&html))
+    }
+
+    fn matches(&self, index: &HeadwordIndex, word: &str, strategy: &str) -> Vec<String> {
+        match strategy {
+            "exact" if index.is_listed(self.mask) => {
+                index.exact(word, self.mask).into_iter().collect()
+            }
+            // a dictionary which can't list its headwords, e.g. a dictd server
+            "exact" => self
+                .define(word)
+                .map(|_| word.to_owned())
+                .into_iter()
+                .collect(),
+            "prefix" => index.prefix(word, self.mask, usize::MAX),
+            "lev" => index
+                .fuzzy(word, 1, self.mask, usize::MAX)
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+pub async fn serve_dict(addr: &str) -> Result<()> {
+    let library = Arc::new(load_library()?);
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    println!(
+        "serving {} dictionaries on dict://{addr}",
+        library.databases.len()
+    );
+    accept(listener, library).await
+}
+
+async fn accept(listener: TcpListener, library: Arc<Library>) -> Result<()> {
+    static SESSION: AtomicU32 = AtomicU32::new(0);
+    loop {
+        let (socket, peer) = listener.accept().await?;
+        let library = library.clone();
+        let session = SESSION.fetch_add(1, Ordering::Relaxed);
+        tokio::spawn(async move {
+            info!("{peer} connected");
+            if let Err(e) = handle(socket, library, session).await {
+                error!("{peer}: {e}");
+            }
+        });
+    }
+}
+
+async fn handle(socket: TcpStream, library: Arc<Library>, session: u32) -> Result<()> {
+    let (reader, mut writer) = socket.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let mut mime = false;
+
+    writer
+        .write_all(
+            format!(
+                "220 mdict-cli-rs <mime> <{}.{session}@mdict-cli-rs>\r\n",
+                std::process::id()
+            )
+            .as_bytes(),
+        )
+        .await?;
+
+    while let Some(line) = lines.next_line().await? {
+        let args = split_quoted(&line);
+        let Some(cmd) = args.first().map(|c| c.to_uppercase()) else {
+            continue;
+        };
+        let response = match (cmd.as_str(), &args[1..]) {
+            ("QUIT", _) => {
+                writer.write_all(b"221 bye\r\n").await?;
+                return Ok(());
+            }
+            ("OPTION", [o]) if o.eq_ignore_ascii_case("MIME") => {
+                mime = true;
+                "250 ok - mime headers will be sent\r\n".to_owned()
+            }
+            ("CLIENT", _) => "250 ok\r\n".to_owned(),
+            ("STATUS", _) => "210 status ok\r\n".to_owned(),
+            ("HELP", _) => text_response(113, "help text follows", HELP, mime),
+            ("SHOW", [what, ..]) => show(&library.databases, what, &args[2..], mime),
+            ("DEFINE", [db, word]) => {
+                let (library, db, word) = (library.clone(), db.clone(), word.clone());
+                tokio::task::spawn_blocking(move || define(&library.databases, &db, &word, mime))
+                    .await?
+            }
+            ("MATCH", [db, strategy, word]) => {
+                let (library, db, strategy, word) =
+                    (library.clone(), db.clone(), strategy.clone(), word.clone());
+                tokio::task::spawn_blocking(move || {
+                    match_words(&library, &db, &strategy, &word, mime)
+                })
+                .await?
+            }
+            ("DEFINE" | "MATCH" | "SHOW" | "OPTION", _) => "501 syntax error\r\n".to_owned(),
+            _ => "500 unknown command\r\n".to_owned(),
+        };
+        writer.write_all(response.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+const HELP: &str = "DEFINE database word
+MATCH database strategy word
+SHOW DB
+SHOW STRAT
+SHOW INFO database
+SHOW SERVER
+OPTION MIME
+CLIENT info
+STATUS
+HELP
+QUIT
+";
+
+/// dot-stuff `text` into a text block
+fn text_block(text: &str, mime: bool) -> String {
+    let mut s = String::new();
+    if mime {
+        s.push_str("Content-Type: text/plain; charset=utf-8\r\n\r\n");
+    }
+    for line in text.lines() {
+        if line.starts_with('.') {
+            s.push('.');
+        }
+        s.push_str(line);
+        s.push_str("\r\n");
+    }
+    s.push_str(".\r\n");
+    s
+}
+
+fn text_response(code: u16, msg: &str, text: &str, mime: bool) -> String {
+    format!("{code} {msg}\r\n{}250 ok\r\n", text_block(text, mime))
+}
+
+fn find<'a>(databases: &'a [Database], db: &str) -> Option<&'a Database> {
+    databases.iter().find(|d| d.id == db)
+}
+
+fn show(databases: &[Database], what: &str, args: &[String], mime: bool) -> String {
+    match (what.to_uppercase().as_str(), args) {
+        ("DB" | "DATABASES", _) => {
+            if databases.is_empty() {
+                return "554 no databases present\r\n".to_owned();
+            }
+            let text: String = databases
+                .iter()
+                .map(|d| format!("{} {}\n", d.id, quote(d.dict.lock().unwrap().name())))
+                .collect();
+            text_response(
+                110,
+                &format!("{} databases present", databases.len()),
+                &text,
+                mime,
+            )
+        }
+        ("STRAT" | "STRATEGIES", _) => {
+            let text: String = STRATEGIES
+                .iter()
+                .map(|(s, desc)| format!("{s} {}\n", quote(desc)))
+                .collect();
+            text_response(
+                111,
+                &format!("{} strategies available", STRATEGIES.len()),
+                &text,
+                mime,
+            )
+        }
+        ("INFO", [db]) => match find(databases, db) {
+            Some(d) => {
+                let dict = d.dict.lock().unwrap();
+                let text = format!("{}\n{}\n", dict.name(), dict.path().display());
+                text_response(112, "database information follows", &text, mime)
+            }
+            None => "550 invalid database, use \"SHOW DB\" for list of databases\r\n".to_owned(),
+        },
+        ("SERVER", _) => text_response(
+            114,
+            "server information follows",
+            &format!("mdict-cli-rs {}\n", crate::build::VERSION),
+            mime,
+        ),
+        _ => "501 syntax error, illegal parameters\r\n".to_owned(),
+    }
+}
+
+/// the databases addressed by `db`, `*` and `!` mean all
+fn targets<'a>(databases: &'a [Database], db: &str) -> Option<Vec<&'a Database>> {
+    match db {
+        "*" | "!" => Some(databases.iter().collect()),
+        _ => find(databases, db).map(|d| vec![d]),
+    }
+}
+
+fn define(databases: &[Database], db: &str, word: &str, mime: bool) -> String {
+    let Some(targets) = targets(databases, db) else {
+        return "550 invalid database, use \"SHOW DB\" for list of databases\r\n".to_owned();
+    };
+    let mut definitions = Vec::new();
+    for d in targets {
+        if let Some(text) = d.define(word) {
+            definitions.push((d, text));
+            if db == "!" {
+                break;
+            }
+        }
+    }
+    if definitions.is_empty() {
+        return "552 no match\r\n".to_owned();
+    }
+
+    let mut s = format!("150 {} definitions retrieved\r\n", definitions.len());
+    for (d, text) in definitions {
+        let _ = write!(
+            s,
+            "151 {} {} {}\r\n{}",
+            quote(word),
+            d.id,
+            quote(d.dict.lock().unwrap().name()),
+            text_block(&text, mime)
+        );
+    }
+    s.push_str("250 ok\r\n");
+    s
+}
+
+fn match_words(library: &Library, db: &str, strategy: &str, word: &str, mime: bool) -> String {
+    // "." is the server default strategy
+    let strategy = if strategy == "." { "lev" } else { strategy };
+    if !STRATEGIES.iter().any(|(s, _)| *s == strategy) {
+        return "551 invalid strategy, use \"SHOW STRAT\" for a list of strategies\r\n".to_owned();
+    }
+    let Some(targets) = targets(&library.databases, db) else {
+        return "550 invalid database, use \"SHOW DB\" for list of databases\r\n".to_owned();
+    };
+    let mut text = String::new();
+    let mut n = 0;
+    for d in targets {
+        let matches = d.matches(&library.index, word, strategy);
+        for m in &matches {
+            let _ = writeln!(text, "{} {}", d.id, quote(m));
+        }
+        n += matches.len();
+        if db == "!" && !matches.is_empty() {
+            break;
+        }
+    }
+    if n == 0 {
+        return "552 no match\r\n".to_owned();
+    }
+    text_response(152, &format!("{n} matches found"), &text, mime)
+}
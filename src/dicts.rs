@@ -0,0 +1,383 @@
+//! Per-dictionary settings of the config: which dictionaries are loaded, in which order
+//! and under which name, and `--dicts` changing them.
+//! `--group` only keeps the dictionaries of a group.
+use crate::config::{self, config, Config};
+use crate::T;
+use anyhow::{anyhow, Context, Result};
+use std::cmp::Reverse;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use toml_edit::{table, value, DocumentMut};
+
+static GROUP: OnceLock<String> = OnceLock::new();
+
+/// only load the dictionaries of group `name`, before they're loaded
+pub fn set_group(name: String) -> Result<()> {
+    let folder = crate::dictionary_dirs()
+        .iter()
+        .any(|dir| dir.join(&name).is_dir());
+    if !folder && !config().groups.contains_key(&name) {
+        return Err(anyhow!(
+            "no group {name} in the config nor folder {name} in the dictionary dirs"
+        ));
+    }
+    let _ = GROUP.set(name);
+    Ok(())
+}
+
+/// the group of `--group`
+pub fn group() -> Option<&'static str> {
+    GROUP.get().map(String::as_str)
+}
+
+fn in_group(dict: &dyn T) -> bool {
+    let Some(name) = group() else {
+        return true;
+    };
+    match config().groups.get(name) {
+        Some(group) => {
+            group
+                .dictionaries
+                .iter()
+                .any(|key| config::is_named(dict.name(), dict.path(), key))
+                || group
+                    .dirs
+                    .iter()
+                    .any(|dir| dict.path().starts_with(config::expand(dir)))
+        }
+        None => crate::dictionary_dirs()
+            .iter()
+            .any(|dir| dict.path().starts_with(dir.join(name))),
+    }
+}
+
+/// drop the disabled dictionaries and those out of the group, sort the others
+/// and give them their alias
+pub fn arrange(mut v: Vec<Box<dyn T>>) -> Vec<Box<dyn T>> {
+    v.retain(|dict| in_group(dict.as_ref()));
+    configure(config(), v)
+}
+
+/// `arrange` with the settings of `config`, the group left aside
+fn configure(config: &Config, mut v: Vec<Box<dyn T>>) -> Vec<Box<dyn T>> {
+    v.retain(|dict| enabled(config, dict.as_ref()));
+    sort(config, &mut v);
+    v.into_iter()
+        .map(|dict| {
+            let settings = config.dictionary(dict.name(), dict.path());
+            if settings.alias.is_none() && !settings.open {
+                return dict;
+            }
+            Box::new(Configured {
+                alias: settings.alias,
+                open: settings.open,
+                dict,
+            }) as Box<dyn T>
+        })
+        .collect()
+}
+
+fn enabled(config: &Config, dict: &dyn T) -> bool {
+    config.dictionary(dict.name(), dict.path()).enabled
+        && !config
+            .disabled
+            .iter()
+            .any(|key| config::is_named(dict.name(), dict.path(), key))
+}
+
+/// highest priority first, then those in `order`, the others keep their place
+fn sort(config: &Config, v: &mut [Box<dyn T>]) {
+    v.sort_by_key(|dict| {
+        let priority = config.dictionary(dict.name(), dict.path()).priority;
+        let position = config
+            .order
+            .iter()
+            .position(|key| config::is_named(dict.name(), dict.path(), key))
+            .unwrap_or(usize::MAX);
+        (Reverse(priority), position)
+    });
+}
+
+/// a dictionary with an alias or shown when the page opens
+struct Configured {
+    alias: Option<String>,
+    open: bool,
+    dict: Box<dyn T>,
+}
+
+impl T for Configured {
+    fn name(&self) -> &str {
+        self.alias.as_deref().unwrap_or_else(|| self.dict.name())
+    }
+
+    fn path(&self) -> &Path {
+        self.dict.path()
+    }
+
+    fn lookup(&self, word: &str, base_dir: &Path) -> Result<PathBuf> {
+        self.dict.lookup(word, base_dir)
+    }
+
+    fn definition(&self, word: &str) -> Result<String> {
+        self.dict.definition(word)
+    }
+
+    fn resource(&self, path: &str) -> Result<Vec<u8>> {
+        self.dict.resource(path)
+    }
+
+    fn headwords(&self) -> Result<Vec<String>> {
+        self.dict.headwords()
+    }
+
+    fn full_text(&self) -> bool {
+        self.dict.full_text()
+    }
+
+    fn suggestions(&self, word: &str) -> Result<Vec<String>> {
+        self.dict.suggestions(word)
+    }
+
+    fn open(&self) -> bool {
+        self.open
+    }
+}
+
+/// `mdict-cli-rs --dicts [enable|disable|open <dict>|priority <dict> <n>|alias <dict> [<alias>]]`,
+/// `dicts` is every dictionary found, the disabled ones included
+pub fn dicts(args: &[String], mut dicts: Vec<Box<dyn T>>) -> Result<()> {
+    let Some(command) = args.first() else {
+        dicts.retain(|dict| in_group(dict.as_ref()));
+        sort(config(), &mut dicts);
+        list(&dicts);
+        return Ok(());
+    };
+    let name = args
+        .get(1)
+        .context("missing dictionary, see `mdict-cli-rs --dicts`")?;
+    let dict = dicts
+        .iter()
+        .find(|d| {
+            config::is_named(d.name(), d.path(), name)
+                || config().dictionary(d.name(), d.path()).alias.as_ref() == Some(name)
+        })
+        .with_context(|| format!("no dictionary {name}, see `mdict-cli-rs --dicts`"))?;
+    // the key it already has, its file name otherwise
+    let key = config()
+        .dictionaries
+        .keys()
+        .find(|key| config::is_named(dict.name(), dict.path(), key))
+        .cloned()
+        .unwrap_or_else(|| {
+            dict.path()
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        });
+
+    let path = config::config_path();
+    let mut doc = match fs::read_to_string(&path) {
+        Ok(s) => s
+            .parse::<DocumentMut>()
+            .with_context(|| format!("Failed to parse {:?}", path))?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => DocumentMut::new(),
+        Err(e) => return Err(e).with_context(|| format!("Failed to open {:?}", path)),
+    };
+    let dictionaries = doc
+        .entry("dictionaries")
+        .or_insert(table())
+        .as_table_mut()
+        .context("dictionaries of the config is not a table")?;
+    dictionaries.set_implicit(true);
+    match command.as_str() {
+        "open" => {
+            for (_, settings) in dictionaries.iter_mut() {
+                if let Some(settings) = settings.as_table_like_mut() {
+                    settings.remove("open");
+                }
+            }
+        }
+        "enable" => {
+            // `disabled` would still hide it
+            if let Some(disabled) = doc.get_mut("disabled").and_then(|d| d.as_array_mut()) {
+                disabled.retain(|key| {
+                    !key.as_str()
+                        .is_some_and(|key| config::is_named(dict.name(), dict.path(), key))
+                });
+            }
+        }
+        _ => {}
+    }
+    let settings = doc["dictionaries"]
+        .as_table_mut()
+        .unwrap()
+        .entry(&key)
+        .or_insert(table())
+        .as_table_mut()
+        .with_context(|| format!("{key} of the config is not a table"))?;
+    match command.as_str() {
+        "enable" => settings["enabled"] = value(true),
+        "disable" => settings["enabled"] = value(false),
+        "open" => settings["open"] = value(true),
+        "priority" => {
+            let priority = args.get(2).context("missing priority")?;
+            let priority: i64 = priority
+                .parse()
+                .with_context(|| format!("bad priority {priority}"))?;
+            settings["priority"] = value(priority);
+        }
+        "alias" => match args.get(2) {
+            Some(alias) => settings["alias"] = value(alias),
+            None => {
+                settings.remove("alias");
+            }
+        },
+        _ => {
+            return Err(anyhow!(
+                "unknown command {command}, expected enable, disable, open, priority or alias"
+            ))
+        }
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory {:?}", parent))?;
+    }
+    fs::write(&path, doc.to_string()).with_context(|| format!("Failed to write {:?}", path))?;
+    println!("{key} saved in {:?}", path);
+    Ok(())
+}
+
+/// a line for each dictionary: priority, state, name and path
+fn list(dicts: &[Box<dyn T>]) {
+    let config = config();
+    for dict in dicts {
+        let settings = config.dictionary(dict.name(), dict.path());
+        let state = if !enabled(config, dict.as_ref()) {
+            "disabled"
+        } else if settings.open {
+            "open"
+        } else {
+            ""
+        };
+        let name = match &settings.alias {
+            Some(alias) => format!("{alias} ({})", dict.name()),
+            None => dict.name().to_owned(),
+        };
+        println!(
+            "{:>4}  {state:<8}  {name:<20}  {:?}",
+            settings.priority,
+            dict.path()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Stub(&'static str, PathBuf);
+
+    impl T for Stub {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn path(&self) -> &Path {
+            &self.1
+        }
+
+        fn lookup(&self, _word: &str, _base_dir: &Path) -> Result<PathBuf> {
+            unimplemented!()
+        }
+
+        fn definition(&self, _word: &str) -> Result<String> {
+            unimplemented!()
+        }
+    }
+
+    /// dictionaries a to e, found in this order
+    fn found() -> Vec<Box<dyn T>> {
+        ["a", "b", "c", "d", "e"]
+            .into_iter()
+            .map(|name| {
+                Box::new(Stub(name, PathBuf::from(format!("/dicts/{name}.mdx")))) as Box<dyn T>
+            })
+            .collect()
+    }
+
+    fn names(config: &str) -> Vec<String> {
+        let config: Config = toml::from_str(config).unwrap();
+        configure(&config, found())
+            .iter()
+            .map(|d| d.name().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn found_order() {
+        assert_eq!(names(""), ["a", "b", "c", "d", "e"]);
+    }
+
+    #[test]
+    fn order_then_priority() {
+        // by name, file name or file stem, the others keep their place after them
+        assert_eq!(names("order = ['d', 'b.mdx']"), ["d", "b", "a", "c", "e"]);
+        assert_eq!(
+            names(
+                "order = ['d', 'b']
+                [dictionaries.e]
+                priority = 1
+                [dictionaries.a]
+                priority = -1"
+            ),
+            ["e", "d", "b", "c", "a"]
+        );
+    }
+
+    #[test]
+    fn enable_disable() {
+        assert_eq!(
+            names(
+                "disabled = ['b', 'c.mdx']
+                [dictionaries.d]
+                enabled = false
+                [dictionaries.c]
+                enabled = true"
+            ),
+            ["a", "e"]
+        );
+    }
+
+    #[test]
+    fn alias() {
+        assert_eq!(
+            names(
+                "order = ['c']
+                [dictionaries.'c.mdx']
+                alias = 'C'
+                open = true"
+            ),
+            ["C", "a", "b", "d", "e"]
+        );
+    }
+
+    #[test]
+    fn unknown_ids() {
+        assert_eq!(
+            names(
+                "order = ['x', 'c', 'y']
+                disabled = ['z']
+                [dictionaries.w]
+                enabled = false
+                priority = 9"
+            ),
+            ["c", "a", "b", "d", "e"]
+        );
+        let e = dicts(&["enable".to_owned(), "x".to_owned()], found()).unwrap_err();
+        assert_eq!(e.to_string(), "no dictionary x, see `mdict-cli-rs --dicts`");
+    }
+}
@@ -0,0 +1,259 @@
+//! Full-text search in the definitions of stardict and mdict dictionaries.
+//!
+//! The definitions, html stripped, are kept in an SQLite FTS5 table in
+//! `<dictionary dir>/.index/fulltext.db`. A dictionary is indexed again when its file changes.
+//!
+//! Chinese has no spaces between words, so the definitions are also kept segmented by jieba,
+//! which backs the reverse lookup of bilingual dictionaries.
+use crate::headword_index::{index_dir, Source};
+use crate::render::html_to_text;
+use crate::T;
+use anyhow::{Context, Result};
+use jieba_rs::Jieba;
+use log::*;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+use sqlx::Row;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// bumped when the tables change, the index is built again
+const SCHEMA_VERSION: i64 = 1;
+
+pub struct FullText {
+    conn: SqlitePool,
+}
+
+/// A headword whose definition matches.
+pub struct Hit {
+    pub word: String,
+    pub dictionary: String,
+    /// the matching part of the definition, terms surrounded by the highlight marks
+    pub snippet: String,
+}
+
+impl fmt::Display for Hit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}): {}",
+            self.word,
+            self.dictionary,
+            self.snippet
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        )
+    }
+}
+
+impl FullText {
+    pub async fn open() -> Result<FullText> {
+        let dir = index_dir();
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create directory {:?}", dir))?;
+        let path = dir.join("fulltext.db");
+        let options = SqliteConnectOptions::new()
+            .filename(&path)
+            .create_if_missing(true);
+        let conn = SqlitePool::connect_with(options)
+            .await
+            .with_context(|| format!("Failed to open {:?}", path))?;
+        let user_version: i64 = sqlx::query("PRAGMA user_version;")
+            .fetch_one(&conn)
+            .await?
+            .get(0);
+        if user_version < SCHEMA_VERSION {
+            sqlx::raw_sql(
+                "
+BEGIN EXCLUSIVE;
+DROP TABLE IF EXISTS definition;
+DROP TABLE IF EXISTS source;
+CREATE TABLE source (
+    id INTEGER PRIMARY KEY NOT NULL,
+    path TEXT UNIQUE NOT NULL,
+    name TEXT NOT NULL,
+    len INTEGER NOT NULL,
+    modified INTEGER NOT NULL
+) STRICT;
+-- tokens is text segmented by spaces
+CREATE VIRTUAL TABLE definition USING fts5(
+    word UNINDEXED,
+    text,
+    tokens,
+    source UNINDEXED,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+PRAGMA user_version = 1;
+COMMIT;
+",
+            )
+            .execute(&conn)
+            .await?;
+        }
+        Ok(FullText { conn })
+    }
+
+    /// index new or changed dictionaries, forget removed ones
+    pub async fn update(&self, dicts: &[Box<dyn T>]) -> Result<()> {
+        let dicts: Vec<&dyn T> = dicts
+            .iter()
+            .map(|d| d.as_ref())
+            .filter(|d| d.full_text())
+            .collect();
+
+        let mut fresh: HashSet<PathBuf> = HashSet::new();
+        for row in sqlx::query("SELECT id, path, len, modified FROM source")
+            .fetch_all(&self.conn)
+            .await?
+        {
+            let id: i64 = row.get(0);
+            let indexed = Source {
+                path: PathBuf::from(row.get::<String, _>(1)),
+                len: row.get::<i64, _>(2) as u64,
+                modified: row.get::<i64, _>(3) as u64,
+            };
+            let unchanged = dicts
+                .iter()
+                .any(|d| d.path() == indexed.path && Source::new(d.path()) == indexed);
+            if unchanged {
+                fresh.insert(indexed.path);
+            } else {
+                self.remove(id).await?;
+            }
+        }
+
+        for dict in dicts {
+            if !fresh.contains(dict.path()) {
+                self.index(dict).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn remove(&self, id: i64) -> Result<()> {
+        let mut tx = self.conn.begin().await?;
+        sqlx::query("DELETE FROM definition WHERE source = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM source WHERE id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn index(&self, dict: &dyn T) -> Result<()> {
+        let source = Source::new(dict.path());
+        let mut words = dict.headwords()?;
+        words.sort();
+        words.dedup();
+        info!("indexing {} definitions of {}", words.len(), dict.name());
+
+        let mut tx = self.conn.begin().await?;
+        let id: i64 = sqlx::query(
+            "INSERT INTO source (path, name, len, modified) VALUES ($1, $2, $3, $4) RETURNING id",
+        )
+        .bind(dict.path().to_string_lossy())
+        .bind(dict.name())
+        .bind(source.len as i64)
+        .bind(source.modified as i64)
+        .fetch_one(&mut *tx)
+        .await?
+        .get(0);
+        for word in words {
+            let Ok(html) = dict.definition(&word) else {
+                continue;
+            };
+            let text = html_to_text(&html);
+            let tokens = jieba().cut_for_search(&text, true).join(" ");
+            sqlx::query(
+                "INSERT INTO definition (word, text, tokens, source) VALUES ($1, $2, $3, $4)",
+            )
+            .bind(&word)
+            .bind(text)
+            .bind(tokens)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// headwords whose definitions contain all terms of `query`, best first
+    pub async fn search(
+        &self,
+        query: &str,
+        (open, close): (&str, &str),
+        limit: u32,
+    ) -> Result<Vec<Hit>> {
+        let Some(terms) = fts_query(query.split_whitespace()) else {
+            return Ok(Vec::new());
+        };
+        let rows = sqlx::query(
+            "
+SELECT word, name, snippet(definition, 1, $2, $3, '…', 16)
+FROM definition JOIN source ON source.id = definition.source
+WHERE definition MATCH $1
+ORDER BY rank
+LIMIT $4",
+        )
+        .bind(format!("text : ({terms})"))
+        .bind(open)
+        .bind(close)
+        .bind(limit)
+        .fetch_all(&self.conn)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| Hit {
+                word: row.get(0),
+                dictionary: row.get(1),
+                snippet: row.get(2),
+            })
+            .collect())
+    }
+
+    /// (headword, dictionary path) of the definitions having `word`, best first
+    pub async fn reverse(&self, word: &str, limit: u32) -> Result<Vec<(String, PathBuf)>> {
+        let Some(terms) = fts_query(jieba().cut(word, true).into_iter()) else {
+            return Ok(Vec::new());
+        };
+        let rows = sqlx::query(
+            "
+SELECT word, path
+FROM definition JOIN source ON source.id = definition.source
+WHERE definition MATCH $1
+ORDER BY rank
+LIMIT $2",
+        )
+        .bind(format!("tokens : ({terms})"))
+        .bind(limit)
+        .fetch_all(&self.conn)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get(0), PathBuf::from(row.get::<String, _>(1))))
+            .collect())
+    }
+}
+
+/// the segmenter of Chinese text, loaded once
+pub fn jieba() -> &'static Jieba {
+    static JIEBA: OnceLock<Jieba> = OnceLock::new();
+    JIEBA.get_or_init(Jieba::new)
+}
+
+/// all the terms, quoted so the fts5 query syntax can't fail
+fn fts_query<'a>(terms: impl Iterator<Item = &'a str>) -> Option<String> {
+    let terms: Vec<String> = terms
+        .filter(|t| t.chars().any(char::is_alphanumeric))
+        .map(|t| format!("\"{}\"", t.replace('"', "\"\"")))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
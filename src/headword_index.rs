@@ -0,0 +1,519 @@
+//! One persistent index of the headwords of all dictionaries.
+//!
+//! The lowercased headwords are kept in an fst map under `<dictionary dir>/.index/`,
+//! or `.index/groups/<group>/` with `--group`,
+//! the low 32 bits of the value of a headword are the index of the list of dictionaries
+//! having it, the lists are kept in `headwords-dicts.json`. The high bits are 0 when the
+//! dictionaries spell it lowercased, else 1 + where its spellings are in
+//! `headwords-spellings.txt`, a line of the spellings of each dictionary of the list
+//! separated by tabs, those of a dictionary separated by `\x1f`.
+//! It's rebuilt when any file of a dictionary is added, removed or modified.
+use crate::cache::replace;
+use crate::dictionary_dir;
+use crate::dicts::group;
+use crate::normalize::normalize;
+use anyhow::{Context, Result};
+use fst::automaton::{Levenshtein, Str};
+use fst::{Automaton, IntoStreamer, Map, MapBuilder, Streamer};
+use log::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// the dictionaries a search keeps
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mask {
+    All,
+    /// the dictionary at this index of the paths the index was opened with
+    Dict(usize),
+}
+
+/// a dictionary file an index is built from, a different len or mtime means it changed
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Source {
+    pub path: PathBuf,
+    pub len: u64,
+    pub modified: u64,
+}
+
+impl Source {
+    pub fn new(path: &Path) -> Source {
+        let meta = fs::metadata(path).ok();
+        Source {
+            path: path.to_path_buf(),
+            len: meta.as_ref().map_or(0, |m| m.len()),
+            modified: meta
+                .and_then(|m| m.modified().ok())
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_secs()),
+        }
+    }
+}
+
+pub struct HeadwordIndex {
+    map: Map<Vec<u8>>,
+    /// the indexes of the dictionaries having a headword, in order, by the value of the headword
+    dicts: Vec<Vec<usize>>,
+    /// the headwords which aren't lowercase, see the module
+    spellings: String,
+}
+
+/// the list of dictionaries of the headword of `value`
+fn list(value: u64) -> usize {
+    (value & 0xffff_ffff) as usize
+}
+
+/// where the indexes are kept, apart for each group of `--group`
+pub fn index_dir() -> PathBuf {
+    let dir = dictionary_dir().join(".index");
+    match group() {
+        Some(group) => dir.join("groups").join(group.replace(['/', '\\'], "_")),
+        None => dir,
+    }
+}
+
+impl HeadwordIndex {
+    /// open the index of the dictionaries read from `files`, `headwords(i)` lists the
+    /// headwords of the i-th one if the index has to be rebuilt
+    pub fn open(
+        files: &[Vec<PathBuf>],
+        headwords: impl Fn(usize) -> Vec<String>,
+    ) -> Result<HeadwordIndex> {
+        Self::open_in(&index_dir(), files, headwords)
+    }
+
+    fn open_in(
+        dir: &Path,
+        files: &[Vec<PathBuf>],
+        headwords: impl Fn(usize) -> Vec<String>,
+    ) -> Result<HeadwordIndex> {
+        let sources: Vec<Vec<Source>> = files
+            .iter()
+            .map(|files| files.iter().map(|p| Source::new(p)).collect())
+            .collect();
+        let fst_path = dir.join("headwords.fst");
+        let manifest_path = dir.join("headwords.json");
+        let dicts_path = dir.join("headwords-dicts.json");
+        let spellings_path = dir.join("headwords-spellings.txt");
+
+        // the manifest is written last, the other files are complete when it's there
+        let fresh = fs::read_to_string(&manifest_path)
+            .ok()
+            .and_then(|s| serde_json::from_str::<Vec<Vec<Source>>>(&s).ok())
+            .is_some_and(|old| old == sources)
+            && fst_path.exists()
+            && dicts_path.exists()
+            && spellings_path.exists();
+        if !fresh {
+            info!("building headword index in {:?}", dir);
+            let _ = fs::remove_file(&manifest_path);
+            // (dictionaries, the spellings of each) by lowercased headword
+            let mut words: BTreeMap<String, (Vec<usize>, Vec<Vec<String>>)> = BTreeMap::new();
+            for i in 0..files.len() {
+                for w in headwords(i) {
+                    let (dicts, spellings) = words.entry(w.to_lowercase()).or_default();
+                    if dicts.last() != Some(&i) {
+                        dicts.push(i);
+                        spellings.push(Vec::new());
+                    }
+                    let spellings = spellings.last_mut().unwrap();
+                    if !spellings.contains(&w) {
+                        spellings.push(w);
+                    }
+                }
+            }
+            // most headwords are in the same few lists of dictionaries
+            let mut lists: HashMap<Vec<usize>, u64> = HashMap::new();
+            let mut dicts: Vec<Vec<usize>> = Vec::new();
+            let mut spellings = String::new();
+            replace(&fst_path, |file| {
+                let mut builder = MapBuilder::new(BufWriter::new(file))?;
+                for (w, (v, cased)) in words {
+                    let list = *lists.entry(v).or_insert_with_key(|v| {
+                        dicts.push(v.clone());
+                        dicts.len() as u64 - 1
+                    });
+                    let spelling = if cased.iter().flatten().all(|s| *s == w) {
+                        0
+                    } else {
+                        let pos = spellings.len() as u64 + 1;
+                        let line: Vec<String> = cased
+                            .iter()
+                            .map(|v| v.join("\x1f").replace(['\t', '\n'], " "))
+                            .collect();
+                        spellings.push_str(&line.join("\t"));
+                        spellings.push('\n');
+                        pos
+                    };
+                    builder.insert(w, spelling << 32 | list)?;
+                }
+                builder.finish()?;
+                Ok(())
+            })?;
+            replace(&dicts_path, |file| {
+                Ok(serde_json::to_writer(BufWriter::new(file), &dicts)?)
+            })?;
+            replace(&spellings_path, |mut file| {
+                Ok(file.write_all(spellings.as_bytes())?)
+            })?;
+            replace(&manifest_path, |file| {
+                Ok(serde_json::to_writer_pretty(file, &sources)?)
+            })?;
+        }
+
+        let bytes =
+            fs::read(&fst_path).with_context(|| format!("Failed to open {:?}", fst_path))?;
+        let dicts = fs::read_to_string(&dicts_path)
+            .with_context(|| format!("Failed to open {:?}", dicts_path))?;
+        let spellings = fs::read_to_string(&spellings_path)
+            .with_context(|| format!("Failed to open {:?}", spellings_path))?;
+        Ok(HeadwordIndex {
+            map: Map::new(bytes)?,
+            dicts: serde_json::from_str(&dicts)
+                .with_context(|| format!("Failed to parse {:?}", dicts_path))?,
+            spellings,
+        })
+    }
+
+    /// the headword `key` as the dictionaries in `mask` spell it, in their order
+    fn spellings<'a>(&'a self, key: &'a str, value: u64, mask: Mask) -> Vec<&'a str> {
+        let pos = (value >> 32) as usize;
+        let Some(line) = pos
+            .checked_sub(1)
+            .and_then(|pos| self.spellings.get(pos..))
+            .and_then(|s| s.split('\n').next())
+        else {
+            return vec![key];
+        };
+        let dicts = self.dicts.get(list(value)).map_or(&[][..], Vec::as_slice);
+        let v: Vec<&str> = line
+            .split('\t')
+            .zip(dicts)
+            .filter(|(_, &i)| mask == Mask::All || mask == Mask::Dict(i))
+            .flat_map(|(spellings, _)| spellings.split('\x1f'))
+            .collect();
+        if v.is_empty() {
+            vec![key]
+        } else {
+            v
+        }
+    }
+
+    /// the first spelling of `key` in `mask`
+    fn headword(&self, key: &[u8], value: u64, mask: Mask) -> String {
+        let key = String::from_utf8_lossy(key);
+        self.spellings(&key, value, mask)[0].to_owned()
+    }
+
+    /// whether a headword of value `value` is in a dictionary of `mask`
+    fn has(&self, value: u64, mask: Mask) -> bool {
+        match mask {
+            Mask::All => true,
+            Mask::Dict(i) => self
+                .dicts
+                .get(list(value))
+                .is_some_and(|v| v.binary_search(&i).is_ok()),
+        }
+    }
+
+    fn search<A: Automaton>(&self, automaton: A, mask: Mask, limit: usize) -> Vec<String> {
+        let mut stream = self.map.search(automaton).into_stream();
+        let mut v = Vec::new();
+        while let Some((key, value)) = stream.next() {
+            if v.len() == limit {
+                break;
+            }
+            if self.has(value, mask) {
+                v.push(self.headword(key, value, mask));
+            }
+        }
+        v
+    }
+
+    /// whether any dictionary has `word`
+    pub fn contains(&self, word: &str) -> bool {
+        self.map.contains_key(word.to_lowercase())
+    }
+
+    /// whether a dictionary in `mask` listed its headwords, see `T::headwords`
+    pub fn is_listed(&self, mask: Mask) -> bool {
+        (0..self.dicts.len()).any(|value| self.has(value as u64, mask))
+    }
+
+    /// the headword which is `word` ignoring case, or normalized, if a dictionary in `mask` has it,
+    /// as a dictionary spells it, `word` itself if one does
+    pub fn exact(&self, word: &str, mask: Mask) -> Option<String> {
+        [word.to_lowercase(), normalize(word)]
+            .into_iter()
+            .find_map(|key| {
+                let value = self.map.get(&key).filter(|&v| self.has(v, mask))?;
+                let spellings = self.spellings(&key, value, mask);
+                let spelling = spellings
+                    .iter()
+                    .find(|s| **s == word)
+                    .unwrap_or(&spellings[0]);
+                Some(spelling.to_string())
+            })
+    }
+
+    /// headwords starting with `prefix`, in order
+    pub fn prefix(&self, prefix: &str, mask: Mask, limit: usize) -> Vec<String> {
+        let prefix = prefix.to_lowercase();
+        self.search(Str::new(&prefix).starts_with(), mask, limit)
+    }
+
+    /// headwords containing `needle`, in order
+    pub fn substring(&self, needle: &str, mask: Mask, limit: usize) -> Vec<String> {
+        let needle = needle.to_lowercase();
+        self.search(Substring::new(needle.as_bytes()), mask, limit)
+    }
+
+    /// headwords within `distance` edits of `word`, in order
+    pub fn fuzzy(
+        &self,
+        word: &str,
+        distance: u32,
+        mask: Mask,
+        limit: usize,
+    ) -> Result<Vec<String>> {
+        let word = word.to_lowercase();
+        let automaton = Levenshtein::new(&word, distance)
+            .with_context(|| format!("{word} is too long for fuzzy search"))?;
+        Ok(self.search(automaton, mask, limit))
+    }
+
+    /// the first `limit` headwords matching `re`, in order, as each dictionary having
+    /// one spells it, with the index of the dictionary
+    pub fn regex(&self, re: &Regex, limit: usize) -> Vec<(String, usize)> {
+        let mut stream = self.map.stream();
+        let mut v = Vec::new();
+        let mut matched = 0;
+        while let Some((key, value)) = stream.next() {
+            if matched == limit {
+                break;
+            }
+            if re.is_match(&String::from_utf8_lossy(key)) {
+                matched += 1;
+                for &i in self.dicts.get(list(value)).into_iter().flatten() {
+                    v.push((self.headword(key, value, Mask::Dict(i)), i));
+                }
+            }
+        }
+        v
+    }
+}
+
+/// matches keys containing the needle, a KMP automaton
+struct Substring<'a> {
+    needle: &'a [u8],
+    /// length of the longest proper prefix of needle[..=i] which is also its suffix
+    fail: Vec<usize>,
+}
+
+impl<'a> Substring<'a> {
+    fn new(needle: &'a [u8]) -> Substring<'a> {
+        let mut fail = vec![0; needle.len()];
+        let mut k = 0;
+        for i in 1..needle.len() {
+            while k > 0 && needle[i] != needle[k] {
+                k = fail[k - 1];
+            }
+            if needle[i] == needle[k] {
+                k += 1;
+            }
+            fail[i] = k;
+        }
+        Substring { needle, fail }
+    }
+}
+
+impl Automaton for Substring<'_> {
+    /// how much of the needle is matched
+    type State = usize;
+
+    fn start(&self) -> usize {
+        0
+    }
+
+    fn is_match(&self, state: &usize) -> bool {
+        *state == self.needle.len()
+    }
+
+    fn can_match(&self, _: &usize) -> bool {
+        true
+    }
+
+    fn will_always_match(&self, state: &usize) -> bool {
+        self.is_match(state)
+    }
+
+    fn accept(&self, state: &usize, byte: u8) -> usize {
+        if self.is_match(state) {
+            return *state;
+        }
+        let mut k = *state;
+        loop {
+            if self.needle[k] == byte {
+                return k + 1;
+            }
+            if k == 0 {
+                return 0;
+            }
+            k = self.fail[k - 1];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    const EN: &[&str] = &["Paris", "apple", "pineapple", "apply", "banana", "NASA"];
+    const FR: &[&str] = &["paris", "pomme", "banane", "Pineapple"];
+
+    /// the index of two dictionaries in `dir`, read from the files `dir/en` and `dir/fr`
+    fn index(dir: &Path) -> HeadwordIndex {
+        let files = [vec![dir.join("en")], vec![dir.join("fr")]];
+        HeadwordIndex::open_in(&dir.join("index"), &files, |i| {
+            [EN, FR][i].iter().map(|w| w.to_string()).collect()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn exact() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = index(dir.path());
+        // as the dictionary spells it
+        assert_eq!(
+            index.exact("PARIS", Mask::Dict(0)).as_deref(),
+            Some("Paris")
+        );
+        assert_eq!(index.exact("nasa", Mask::All).as_deref(), Some("NASA"));
+        // the word itself when a dictionary spells it so
+        assert_eq!(index.exact("paris", Mask::All).as_deref(), Some("paris"));
+        assert_eq!(index.exact("Paris", Mask::All).as_deref(), Some("Paris"));
+        assert_eq!(index.exact("apple", Mask::All).as_deref(), Some("apple"));
+        assert_eq!(
+            index.exact("PINEAPPLE", Mask::Dict(1)).as_deref(),
+            Some("Pineapple")
+        );
+        assert_eq!(index.exact("pomme", Mask::Dict(0)), None);
+        assert_eq!(index.exact("pear", Mask::All), None);
+        assert!(index.contains("APPLE"));
+    }
+
+    #[test]
+    fn prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = index(dir.path());
+        assert_eq!(index.prefix("AP", Mask::All, 10), ["apple", "apply"]);
+        assert_eq!(index.prefix("ba", Mask::Dict(1), 10), ["banane"]);
+        assert_eq!(index.prefix("", Mask::All, 2), ["apple", "apply"]);
+    }
+
+    #[test]
+    fn substring() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = index(dir.path());
+        // the spelling of the first dictionary in the mask
+        assert_eq!(
+            index.substring("apple", Mask::All, 10),
+            ["apple", "pineapple"]
+        );
+        assert_eq!(index.substring("apple", Mask::Dict(1), 10), ["Pineapple"]);
+        assert_eq!(index.substring("ana", Mask::All, 10), ["banana", "banane"]);
+        // the automaton falls back, "nan" after "bana"
+        assert_eq!(index.substring("nan", Mask::All, 10), ["banana", "banane"]);
+        assert_eq!(index.substring("aa", Mask::All, 10), Vec::<String>::new());
+        assert_eq!(index.substring("", Mask::Dict(1), 10).len(), FR.len());
+    }
+
+    #[test]
+    fn kmp() {
+        let needle = b"abab";
+        let s = Substring::new(needle);
+        assert_eq!(s.fail, [0, 0, 1, 2]);
+        let run = |hay: &[u8]| {
+            let state = hay.iter().fold(s.start(), |state, &b| s.accept(&state, b));
+            s.is_match(&state)
+        };
+        assert!(run(b"aababab"));
+        assert!(run(b"ababx"));
+        assert!(!run(b"abaab"));
+    }
+
+    #[test]
+    fn fuzzy() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = index(dir.path());
+        assert_eq!(index.fuzzy("aple", 1, Mask::All, 10).unwrap(), ["apple"]);
+        assert_eq!(
+            index.fuzzy("appla", 1, Mask::All, 10).unwrap(),
+            ["apple", "apply"]
+        );
+        assert_eq!(
+            index.fuzzy("banan", 1, Mask::Dict(0), 10).unwrap(),
+            ["banana"]
+        );
+    }
+
+    #[test]
+    fn masks() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = index(dir.path());
+        let re = Regex::new("^p").unwrap();
+        let found = index.regex(&re, 10);
+        let found: Vec<(&str, usize)> = found.iter().map(|(w, i)| (w.as_str(), *i)).collect();
+        assert_eq!(
+            found,
+            [
+                ("Paris", 0),
+                ("paris", 1),
+                ("pineapple", 0),
+                ("Pineapple", 1),
+                ("pomme", 1)
+            ]
+        );
+        assert_eq!(index.regex(&re, 1).len(), 2);
+        assert!(index.is_listed(Mask::All));
+        assert!(index.is_listed(Mask::Dict(1)));
+        assert!(!index.is_listed(Mask::Dict(2)));
+    }
+
+    #[test]
+    fn rebuilt_when_stale() {
+        let dir = tempfile::tempdir().unwrap();
+        let en = dir.path().join("en.idx");
+        let dict = dir.path().join("en.dict");
+        fs::write(&en, "1").unwrap();
+        fs::write(&dict, "1").unwrap();
+        let files = [vec![en.clone(), dict.clone()]];
+        let built = Cell::new(0);
+        let open = || {
+            HeadwordIndex::open_in(&dir.path().join("index"), &files, |_| {
+                built.set(built.get() + 1);
+                vec!["apple".to_owned()]
+            })
+            .unwrap()
+        };
+
+        open();
+        open();
+        assert_eq!(built.get(), 1);
+        // any file of a dictionary
+        fs::write(&en, "22").unwrap();
+        open();
+        assert_eq!(built.get(), 2);
+        // a broken index without its manifest
+        fs::write(dir.path().join("index/headwords.fst"), "").unwrap();
+        fs::remove_file(dir.path().join("index/headwords.json")).unwrap();
+        assert!(open().contains("apple"));
+        assert_eq!(built.get(), 3);
+    }
+}
@@ -0,0 +1,454 @@
+//! Stems of inflected words from Hunspell `.aff`/`.dic` pairs in the dictionary dir.
+//!
+//! Only the affix rules are used, to strip a prefix, a suffix or two suffixes
+//! off a word until a stem of the `.dic` accepts them, as GoldenDict does.
+use anyhow::{anyhow, Context, Result};
+use encoding_rs::{Encoding, UTF_8};
+use log::*;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+type Flag = u32;
+
+#[derive(Debug, Clone, Copy)]
+enum FlagType {
+    /// one byte, the default
+    Char,
+    /// two characters
+    Long,
+    /// decimal numbers separated by commas
+    Num,
+    /// one unicode character
+    Utf8,
+}
+
+impl FlagType {
+    fn parse(self, s: &str) -> Vec<Flag> {
+        match self {
+            FlagType::Char | FlagType::Utf8 => s.chars().map(|c| c as Flag).collect(),
+            FlagType::Long => s
+                .chars()
+                .collect::<Vec<_>>()
+                .chunks(2)
+                .map(|c| c.iter().fold(0, |acc, &c| (acc << 16) | c as Flag))
+                .collect(),
+            FlagType::Num => s.split(',').filter_map(|n| n.trim().parse().ok()).collect(),
+        }
+    }
+}
+
+/// one character of a condition
+#[derive(Debug)]
+enum Cond {
+    Any,
+    Set { chars: Vec<char>, negated: bool },
+}
+
+impl Cond {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Cond::Any => true,
+            Cond::Set { chars, negated } => chars.contains(&c) != *negated,
+        }
+    }
+}
+
+fn parse_condition(s: &str) -> Vec<Cond> {
+    let mut v = Vec::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => v.push(Cond::Any),
+            '[' => {
+                let mut set = Vec::new();
+                let mut negated = false;
+                for c in chars.by_ref() {
+                    match c {
+                        ']' => break,
+                        '^' if set.is_empty() && !negated => negated = true,
+                        c => set.push(c),
+                    }
+                }
+                v.push(Cond::Set {
+                    chars: set,
+                    negated,
+                });
+            }
+            c => v.push(Cond::Set {
+                chars: vec![c],
+                negated: false,
+            }),
+        }
+    }
+    v
+}
+
+#[derive(Debug)]
+struct Affix {
+    flag: Flag,
+    cross_product: bool,
+    strip: String,
+    add: String,
+    /// flags the affixed word gets, for twofold suffixes
+    continuation: Vec<Flag>,
+    condition: Vec<Cond>,
+}
+
+impl Affix {
+    /// whether the condition matches the start (prefix) or the end (suffix) of `base`
+    fn condition_matches(&self, base: &str, prefix: bool) -> bool {
+        let n = self.condition.len();
+        let chars: Vec<char> = if prefix {
+            base.chars().take(n).collect()
+        } else {
+            let mut v: Vec<char> = base.chars().rev().take(n).collect();
+            v.reverse();
+            v
+        };
+        chars.len() == n
+            && self
+                .condition
+                .iter()
+                .zip(chars)
+                .all(|(c, ch)| c.matches(ch))
+    }
+
+    /// `word` without this suffix
+    fn strip_suffix(&self, word: &str) -> Option<String> {
+        let rest = word.strip_suffix(&self.add)?;
+        if rest.is_empty() && self.strip.is_empty() {
+            return None;
+        }
+        let base = format!("{rest}{}", self.strip);
+        self.condition_matches(&base, false).then_some(base)
+    }
+
+    /// `word` without this prefix
+    fn strip_prefix(&self, word: &str) -> Option<String> {
+        let rest = word.strip_prefix(&self.add)?;
+        if rest.is_empty() && self.strip.is_empty() {
+            return None;
+        }
+        let base = format!("{}{rest}", self.strip);
+        self.condition_matches(&base, true).then_some(base)
+    }
+}
+
+pub struct Hunspell {
+    prefixes: Vec<Affix>,
+    suffixes: Vec<Affix>,
+    /// stem -> flags
+    words: HashMap<String, Vec<Flag>>,
+}
+
+/// `SET` names which are not encoding labels
+fn encoding(name: &str) -> &'static Encoding {
+    let label = name.trim().to_lowercase();
+    let label = label.strip_prefix("microsoft-").unwrap_or(&label);
+    let label = if label.starts_with("tis620") {
+        "tis-620"
+    } else {
+        label
+    };
+    Encoding::for_label(label.as_bytes()).unwrap_or(UTF_8)
+}
+
+impl Hunspell {
+    /// `path` is the `.dic`, the `.aff` is next to it
+    pub fn new(path: &Path) -> Result<Hunspell> {
+        let aff_path = path.with_extension("aff");
+        let aff = fs::read(&aff_path).with_context(|| format!("Failed to open {:?}", aff_path))?;
+        let dic = fs::read(path).with_context(|| format!("Failed to open {:?}", path))?;
+
+        // SET is ascii, find it before decoding
+        let set = aff
+            .split(|&b| b == b'\n')
+            .find_map(|line| line.strip_prefix(b"SET "))
+            .map(|name| encoding(&String::from_utf8_lossy(name)))
+            .unwrap_or(UTF_8);
+        let (aff, _, _) = set.decode(&aff);
+        let (dic, _, _) = set.decode(&dic);
+
+        let mut flag_type = FlagType::Char;
+        let mut aliases: Vec<Vec<Flag>> = Vec::new();
+        let mut prefixes = Vec::new();
+        let mut suffixes = Vec::new();
+        // (PFX or SFX, flag) -> cross product, from the headers of the affix blocks
+        let mut headers: HashMap<(String, String), bool> = HashMap::new();
+
+        for line in aff.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields[..] {
+                ["FLAG", t, ..] => {
+                    flag_type = match t {
+                        "long" => FlagType::Long,
+                        "num" => FlagType::Num,
+                        "UTF-8" => FlagType::Utf8,
+                        _ => FlagType::Char,
+                    }
+                }
+                // AF 5 is the count, the aliases follow and are numbered from 1,
+                // the words after them are comments
+                ["AF", count, ..] if aliases.is_empty() && count.parse::<usize>().is_ok() => {
+                    aliases.push(Vec::new());
+                }
+                ["AF", flags, ..] => aliases.push(flag_type.parse(flags)),
+                [kind @ ("PFX" | "SFX"), flag, cross, count]
+                    if count.parse::<usize>().is_ok() && matches!(cross, "Y" | "N") =>
+                {
+                    headers.insert((kind.to_owned(), flag.to_owned()), cross == "Y");
+                }
+                [kind @ ("PFX" | "SFX"), flag, strip, add, ref rest @ ..] => {
+                    let Some(&cross_product) = headers.get(&(kind.to_owned(), flag.to_owned()))
+                    else {
+                        continue;
+                    };
+                    let Some(&flag) = flag_type.parse(flag).first() else {
+                        continue;
+                    };
+                    let (add, continuation) = match add.split_once('/') {
+                        Some((add, flags)) => (add, resolve(flags, flag_type, &aliases)),
+                        None => (add, Vec::new()),
+                    };
+                    let affix = Affix {
+                        flag,
+                        cross_product,
+                        strip: if strip == "0" { "" } else { strip }.to_owned(),
+                        add: if add == "0" { "" } else { add }.to_owned(),
+                        continuation,
+                        condition: parse_condition(rest.first().copied().unwrap_or(".")),
+                    };
+                    if kind == "PFX" {
+                        prefixes.push(affix);
+                    } else {
+                        suffixes.push(affix);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut words: HashMap<String, Vec<Flag>> = HashMap::new();
+        // the first line is the number of words
+        for line in dic.lines().skip(1) {
+            let entry = line.split(['\t', ' ']).next().unwrap_or_default();
+            if entry.is_empty() {
+                continue;
+            }
+            let (word, flags) = match entry.split_once('/') {
+                Some((word, flags)) => (word, resolve(flags, flag_type, &aliases)),
+                None => (entry, Vec::new()),
+            };
+            words.entry(word.to_owned()).or_default().extend(flags);
+        }
+
+        if words.is_empty() {
+            return Err(anyhow!("no word in {:?}", path));
+        }
+        Ok(Hunspell {
+            prefixes,
+            suffixes,
+            words,
+        })
+    }
+
+    fn has(&self, word: &str, flag: Flag) -> bool {
+        self.words.get(word).is_some_and(|f| f.contains(&flag))
+    }
+
+    /// stems of `word` in the `.dic`, `word` itself excluded
+    pub fn stems(&self, word: &str) -> Vec<String> {
+        let mut v = Vec::new();
+        for sfx in &self.suffixes {
+            let Some(base) = sfx.strip_suffix(word) else {
+                continue;
+            };
+            if self.has(&base, sfx.flag) {
+                v.push(base.clone());
+            }
+            // a prefix and a suffix
+            if sfx.cross_product {
+                for pfx in self.prefixes.iter().filter(|p| p.cross_product) {
+                    if let Some(stem) = pfx.strip_prefix(&base) {
+                        if self.has(&stem, sfx.flag) && self.has(&stem, pfx.flag) {
+                            v.push(stem);
+                        }
+                    }
+                }
+            }
+            // two suffixes, the inner one continued by the outer one
+            for inner in self
+                .suffixes
+                .iter()
+                .filter(|s| s.continuation.contains(&sfx.flag))
+            {
+                if let Some(stem) = inner.strip_suffix(&base) {
+                    if self.has(&stem, inner.flag) {
+                        v.push(stem);
+                    }
+                }
+            }
+        }
+        for pfx in &self.prefixes {
+            if let Some(stem) = pfx.strip_prefix(word) {
+                if self.has(&stem, pfx.flag) {
+                    v.push(stem);
+                }
+            }
+        }
+        v.retain(|s| s != word);
+        v
+    }
+}
+
+/// flags, or the number of an `AF` alias
+fn resolve(flags: &str, flag_type: FlagType, aliases: &[Vec<Flag>]) -> Vec<Flag> {
+    if !aliases.is_empty() {
+        if let Ok(n) = flags.parse::<usize>() {
+            return aliases.get(n).cloned().unwrap_or_default();
+        }
+    }
+    flag_type.parse(flags)
+}
+
+/// every `.dic` with an `.aff` under `dir`
+pub fn load(dir: &Path) -> Vec<Hunspell> {
+    let mut v = Vec::new();
+    for entry in WalkDir::new(dir).follow_links(true) {
+        let Ok(entry) = entry else { continue };
+        let path = entry.path();
+        if path.extension().and_then(OsStr::to_str) != Some("dic")
+            || !path.with_extension("aff").is_file()
+        {
+            continue;
+        }
+        match Hunspell::new(path) {
+            Ok(h) => v.push(h),
+            Err(e) => error!("{e}"),
+        }
+    }
+    v
+}
+
+/// stems of `word` in all `dictionaries`, trying its lowercase and capitalized forms too
+pub fn stems(dictionaries: &[Hunspell], word: &str) -> Vec<String> {
+    let lower = word.to_lowercase();
+    let mut chars = lower.chars();
+    let capitalized: String = chars
+        .next()
+        .map(|c| c.to_uppercase().chain(chars).collect())
+        .unwrap_or_default();
+
+    let mut v: Vec<String> = Vec::new();
+    for form in [word, &lower, &capitalized] {
+        for h in dictionaries {
+            for stem in h.stems(form) {
+                if stem != word && !v.contains(&stem) {
+                    v.push(stem);
+                }
+            }
+        }
+    }
+    v
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dictionary(aff: &[u8], dic: &[u8]) -> Hunspell {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("t.aff"), aff).unwrap();
+        fs::write(dir.path().join("t.dic"), dic).unwrap();
+        Hunspell::new(&dir.path().join("t.dic")).unwrap()
+    }
+
+    /// long flags by `AF` aliases, "lich" takes the flags of alias 1 after it
+    const AFF: &str = "SET UTF-8
+FLAG long
+AF 2
+AF AaCc
+AF Bb
+SFX Aa Y 1
+SFX Aa 0 en .
+SFX Bb Y 1
+SFX Bb 0 lich/1 .
+PFX Cc Y 1
+PFX Cc 0 un .
+";
+
+    #[test]
+    fn aliases() {
+        let h = dictionary(AFF.as_bytes(), b"3\nhaus/1\nfreund/2\nkind\n");
+        assert_eq!(h.stems("hausen"), ["haus"]);
+        assert_eq!(h.stems("unhaus"), ["haus"]);
+        // a prefix and a suffix
+        assert_eq!(h.stems("unhausen"), ["haus"]);
+        assert!(h.stems("freunden").is_empty());
+        assert!(h.stems("kinden").is_empty());
+        assert!(h.stems("haus").is_empty());
+    }
+
+    #[test]
+    fn alias_comments() {
+        let aff = AFF
+            .replace("AF 2\n", "AF 2 # two aliases\n")
+            .replace("AF Bb\n", "AF Bb # 2\n");
+        let h = dictionary(aff.as_bytes(), b"3\nhaus/1\nfreund/2\nkind\n");
+        assert_eq!(h.stems("hausen"), ["haus"]);
+        assert_eq!(h.stems("freundlichen"), ["freund"]);
+    }
+
+    #[test]
+    fn twofold_suffixes() {
+        let h = dictionary(AFF.as_bytes(), b"3\nhaus/1\nfreund/2\nkind\n");
+        assert_eq!(h.stems("freundlich"), ["freund"]);
+        assert_eq!(h.stems("freundlichen"), ["freund"]);
+        // the inner suffix doesn't continue "haus"
+        assert!(h.stems("hauslichen").is_empty());
+    }
+
+    #[test]
+    fn conditions() {
+        let aff = b"SET ISO8859-1
+FLAG num
+SFX 1 Y 3
+SFX 1 y ies [^aeiou]y
+SFX 1 0 s [aeiou]y
+SFX 1 0 s [^y]
+";
+        let h = dictionary(aff, b"3\ncity/1\nboy/1\ncaf\xe9/1\n");
+        assert_eq!(h.stems("cities"), ["city"]);
+        assert_eq!(h.stems("boys"), ["boy"]);
+        assert!(h.stems("boies").is_empty());
+        assert!(h.stems("citys").is_empty());
+        assert_eq!(h.stems("cafés"), ["café"]);
+    }
+
+    #[test]
+    fn flag_types() {
+        assert_eq!(FlagType::Char.parse("AB"), ['A' as Flag, 'B' as Flag]);
+        assert_eq!(
+            FlagType::Long.parse("AaBb"),
+            [
+                ('A' as Flag) << 16 | 'a' as Flag,
+                ('B' as Flag) << 16 | 'b' as Flag
+            ]
+        );
+        assert_eq!(FlagType::Num.parse("1, 20,x"), [1, 20]);
+        assert_eq!(
+            resolve("2", FlagType::Char, &[vec![], vec![1], vec![7]]),
+            [7]
+        );
+        assert_eq!(resolve("2", FlagType::Char, &[]), ['2' as Flag]);
+    }
+
+    #[test]
+    fn capitalized() {
+        let h = dictionary(AFF.as_bytes(), b"3\nHaus/1\nfreund/2\nkind\n");
+        assert!(stems(&[h], "hausen").contains(&"Haus".to_owned()));
+    }
+}
@@ -0,0 +1,471 @@
+//! Japanese forms of a word: romaji to kana, hiragana ↔ katakana, and deinflection
+//! of verb and adjective conjugations, so words can be looked up without an IME.
+
+/// at most this many forms of a word are tried
+const LIMIT: usize = 16;
+
+/// romaji syllables, Hepburn and Kunrei-shiki, longest first when matching
+const ROMAJI: &[(&str, &str)] = &[
+    ("a", "あ"),
+    ("i", "い"),
+    ("u", "う"),
+    ("e", "え"),
+    ("o", "お"),
+    ("ka", "か"),
+    ("ki", "き"),
+    ("ku", "く"),
+    ("ke", "け"),
+    ("ko", "こ"),
+    ("kya", "きゃ"),
+    ("kyu", "きゅ"),
+    ("kyo", "きょ"),
+    ("ga", "が"),
+    ("gi", "ぎ"),
+    ("gu", "ぐ"),
+    ("ge", "げ"),
+    ("go", "ご"),
+    ("gya", "ぎゃ"),
+    ("gyu", "ぎゅ"),
+    ("gyo", "ぎょ"),
+    ("sa", "さ"),
+    ("shi", "し"),
+    ("si", "し"),
+    ("su", "す"),
+    ("se", "せ"),
+    ("so", "そ"),
+    ("sha", "しゃ"),
+    ("shu", "しゅ"),
+    ("sho", "しょ"),
+    ("she", "しぇ"),
+    ("sya", "しゃ"),
+    ("syu", "しゅ"),
+    ("syo", "しょ"),
+    ("za", "ざ"),
+    ("ji", "じ"),
+    ("zi", "じ"),
+    ("zu", "ず"),
+    ("ze", "ぜ"),
+    ("zo", "ぞ"),
+    ("ja", "じゃ"),
+    ("ju", "じゅ"),
+    ("jo", "じょ"),
+    ("je", "じぇ"),
+    ("zya", "じゃ"),
+    ("zyu", "じゅ"),
+    ("zyo", "じょ"),
+    ("jya", "じゃ"),
+    ("jyu", "じゅ"),
+    ("jyo", "じょ"),
+    ("ta", "た"),
+    ("chi", "ち"),
+    ("ti", "ち"),
+    ("tsu", "つ"),
+    ("tu", "つ"),
+    ("te", "て"),
+    ("to", "と"),
+    ("cha", "ちゃ"),
+    ("chu", "ちゅ"),
+    ("cho", "ちょ"),
+    ("che", "ちぇ"),
+    ("tya", "ちゃ"),
+    ("tyu", "ちゅ"),
+    ("tyo", "ちょ"),
+    ("da", "だ"),
+    ("di", "ぢ"),
+    ("du", "づ"),
+    ("de", "で"),
+    ("do", "ど"),
+    ("na", "な"),
+    ("ni", "に"),
+    ("nu", "ぬ"),
+    ("ne", "ね"),
+    ("no", "の"),
+    ("nya", "にゃ"),
+    ("nyu", "にゅ"),
+    ("nyo", "にょ"),
+    ("ha", "は"),
+    ("hi", "ひ"),
+    ("fu", "ふ"),
+    ("hu", "ふ"),
+    ("he", "へ"),
+    ("ho", "ほ"),
+    ("hya", "ひゃ"),
+    ("hyu", "ひゅ"),
+    ("hyo", "ひょ"),
+    ("fa", "ふぁ"),
+    ("fi", "ふぃ"),
+    ("fe", "ふぇ"),
+    ("fo", "ふぉ"),
+    ("ba", "ば"),
+    ("bi", "び"),
+    ("bu", "ぶ"),
+    ("be", "べ"),
+    ("bo", "ぼ"),
+    ("bya", "びゃ"),
+    ("byu", "びゅ"),
+    ("byo", "びょ"),
+    ("pa", "ぱ"),
+    ("pi", "ぴ"),
+    ("pu", "ぷ"),
+    ("pe", "ぺ"),
+    ("po", "ぽ"),
+    ("pya", "ぴゃ"),
+    ("pyu", "ぴゅ"),
+    ("pyo", "ぴょ"),
+    ("ma", "ま"),
+    ("mi", "み"),
+    ("mu", "む"),
+    ("me", "め"),
+    ("mo", "も"),
+    ("mya", "みゃ"),
+    ("myu", "みゅ"),
+    ("myo", "みょ"),
+    ("ya", "や"),
+    ("yu", "ゆ"),
+    ("yo", "よ"),
+    ("ra", "ら"),
+    ("ri", "り"),
+    ("ru", "る"),
+    ("re", "れ"),
+    ("ro", "ろ"),
+    ("rya", "りゃ"),
+    ("ryu", "りゅ"),
+    ("ryo", "りょ"),
+    ("wa", "わ"),
+    ("wi", "うぃ"),
+    ("we", "うぇ"),
+    ("wo", "を"),
+    ("va", "ゔぁ"),
+    ("vi", "ゔぃ"),
+    ("vu", "ゔ"),
+    ("ve", "ゔぇ"),
+    ("vo", "ゔぉ"),
+    ("-", "ー"),
+];
+
+/// `word` in hiragana, `None` if it isn't romaji
+fn romaji_to_hiragana(word: &str) -> Option<String> {
+    // macrons are long vowels
+    let word: String = word
+        .to_lowercase()
+        .chars()
+        .flat_map(|c| match c {
+            'ā' | 'â' => vec!['a', 'a'],
+            'ī' | 'î' => vec!['i', 'i'],
+            'ū' | 'û' => vec!['u', 'u'],
+            'ē' | 'ê' => vec!['e', 'e'],
+            'ō' | 'ô' => vec!['o', 'u'],
+            c => vec![c],
+        })
+        .collect();
+    if !word
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c == '-' || c == '\'')
+    {
+        return None;
+    }
+
+    let b = word.as_bytes();
+    let mut kana = String::new();
+    let mut i = 0;
+    while i < b.len() {
+        let next = b.get(i + 1).copied();
+        let vowel = |c: Option<&u8>| matches!(c, Some(b'a' | b'i' | b'u' | b'e' | b'o' | b'y'));
+        // ん before a consonant, at the end, or as n' and nn, konnichiwa is こんにちわ
+        if b[i] == b'n' && !vowel(next.as_ref()) {
+            kana.push('ん');
+            i += match next {
+                Some(b'\'') => 2,
+                Some(b'n') if !vowel(b.get(i + 2)) => 2,
+                _ => 1,
+            };
+            continue;
+        }
+        // shimbun is しんぶん
+        if b[i] == b'm' && matches!(next, Some(b'b' | b'p' | b'm')) {
+            kana.push('ん');
+            i += 1;
+            continue;
+        }
+        // a doubled consonant is a small tsu, also tch
+        if next == Some(b[i]) && !b"aiueon-'".contains(&b[i]) || b[i] == b't' && next == Some(b'c')
+        {
+            kana.push('っ');
+            i += 1;
+            continue;
+        }
+        let (romaji, k) = (1..=3)
+            .rev()
+            .filter_map(|n| word.get(i..i + n))
+            .find_map(|s| ROMAJI.iter().find(|(r, _)| *r == s))?;
+        kana.push_str(k);
+        i += romaji.len();
+    }
+    Some(kana)
+}
+
+fn is_kana(c: char) -> bool {
+    matches!(c, '\u{3041}'..='\u{3096}' | '\u{30a1}'..='\u{30fa}' | 'ー')
+}
+
+fn to_katakana(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '\u{3041}'..='\u{3096}' => char::from_u32(c as u32 + 0x60).unwrap(),
+            c => c,
+        })
+        .collect()
+}
+
+fn to_hiragana(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '\u{30a1}'..='\u{30f6}' => char::from_u32(c as u32 - 0x60).unwrap(),
+            c => c,
+        })
+        .collect()
+}
+
+/// the u, i, a, e and o forms of each godan verb ending
+const GODAN: &[[&str; 5]] = &[
+    ["う", "い", "わ", "え", "お"],
+    ["く", "き", "か", "け", "こ"],
+    ["ぐ", "ぎ", "が", "げ", "ご"],
+    ["す", "し", "さ", "せ", "そ"],
+    ["つ", "ち", "た", "て", "と"],
+    ["ぬ", "に", "な", "ね", "の"],
+    ["ぶ", "び", "ば", "べ", "ぼ"],
+    ["む", "み", "ま", "め", "も"],
+    ["る", "り", "ら", "れ", "ろ"],
+];
+
+/// endings following the i form of a godan verb or the stem of an ichidan verb
+const STEM: &[&str] = &[
+    "ます",
+    "ました",
+    "ません",
+    "ませんでした",
+    "ましょう",
+    "たい",
+    "たくない",
+    "たかった",
+    "ながら",
+    "なさい",
+];
+
+/// endings following the a form of a godan verb or the stem of an ichidan verb
+const NEGATIVE: &[&str] = &["ない", "なかった", "なくて", "ず", "れる", "せる"];
+
+/// (inflected, base) endings which aren't built from the tables above
+const ENDINGS: &[(&str, &str)] = &[
+    // te and ta forms of godan verbs
+    ("った", "う"),
+    ("った", "つ"),
+    ("った", "る"),
+    ("って", "う"),
+    ("って", "つ"),
+    ("って", "る"),
+    ("いた", "く"),
+    ("いて", "く"),
+    ("いだ", "ぐ"),
+    ("いで", "ぐ"),
+    ("した", "す"),
+    ("して", "す"),
+    ("んだ", "む"),
+    ("んだ", "ぶ"),
+    ("んだ", "ぬ"),
+    ("んで", "む"),
+    ("んで", "ぶ"),
+    ("んで", "ぬ"),
+    ("いった", "いく"),
+    ("いって", "いく"),
+    // ichidan verbs
+    ("た", "る"),
+    ("て", "る"),
+    ("られる", "る"),
+    ("させる", "る"),
+    ("よう", "る"),
+    ("れば", "る"),
+    ("ろ", "る"),
+    // する and くる
+    ("した", "する"),
+    ("して", "する"),
+    ("しない", "する"),
+    ("します", "する"),
+    ("しました", "する"),
+    ("しよう", "する"),
+    ("される", "する"),
+    ("させる", "する"),
+    ("できる", "する"),
+    ("きた", "くる"),
+    ("きて", "くる"),
+    ("こない", "くる"),
+    ("きます", "くる"),
+    ("きました", "くる"),
+    ("こよう", "くる"),
+    ("来た", "来る"),
+    ("来て", "来る"),
+    ("来ない", "来る"),
+    ("来ます", "来る"),
+    // i adjectives
+    ("かった", "い"),
+    ("くない", "い"),
+    ("くなかった", "い"),
+    ("くて", "い"),
+    ("く", "い"),
+    ("ければ", "い"),
+    ("さ", "い"),
+    ("そう", "い"),
+];
+
+/// (inflected, base) endings of all conjugations
+fn endings() -> Vec<(String, &'static str)> {
+    let mut v: Vec<(String, &str)> = Vec::new();
+    for [u, i, a, e, o] in GODAN {
+        v.extend(STEM.iter().map(|s| (format!("{i}{s}"), *u)));
+        v.extend(NEGATIVE.iter().map(|s| (format!("{a}{s}"), *u)));
+        // potential, conditional, volitional
+        v.push((format!("{e}る"), *u));
+        v.push((format!("{e}ば"), *u));
+        v.push((format!("{o}う"), *u));
+    }
+    v.extend(STEM.iter().chain(NEGATIVE).map(|s| (s.to_string(), "る")));
+    v.extend(ENDINGS.iter().map(|(i, b)| (i.to_string(), *b)));
+    // the longest ending is the most likely
+    v.sort_by_key(|(inflected, _)| std::cmp::Reverse(inflected.chars().count()));
+    v
+}
+
+/// dictionary forms `word` may be a conjugation of, two conjugations deep
+fn deinflect(word: &str) -> Vec<String> {
+    let endings = endings();
+    let mut v: Vec<String> = Vec::new();
+    let mut last = vec![word.to_owned()];
+    for _ in 0..2 {
+        let mut next = Vec::new();
+        for w in &last {
+            for (inflected, base) in &endings {
+                let Some(stem) = w.strip_suffix(inflected.as_str()) else {
+                    continue;
+                };
+                let base = format!("{stem}{base}");
+                // a single kana is no word, た is not a conjugation of る
+                if base.chars().count() > 1 && base != word && !v.contains(&base) {
+                    v.push(base.clone());
+                    next.push(base);
+                }
+            }
+        }
+        last = next;
+    }
+    v
+}
+
+/// `word` in kana, its other kana script and its dictionary forms, `word` itself excluded
+pub fn variants(word: &str) -> Vec<String> {
+    let mut v = Vec::new();
+    if let Some(hiragana) = romaji_to_hiragana(word) {
+        v.push(hiragana.clone());
+        v.push(to_katakana(&hiragana));
+        v.extend(deinflect(&hiragana));
+    } else if word.chars().any(is_kana) {
+        v.push(to_hiragana(word));
+        v.push(to_katakana(word));
+        v.extend(deinflect(word));
+    }
+
+    let mut seen = vec![word.to_owned()];
+    v.retain(|w| {
+        if seen.contains(w) {
+            false
+        } else {
+            seen.push(w.clone());
+            true
+        }
+    });
+    v.truncate(LIMIT);
+    v
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kana(word: &str) -> String {
+        romaji_to_hiragana(word).unwrap()
+    }
+
+    #[test]
+    fn romaji() {
+        assert_eq!(kana("sushi"), "すし");
+        assert_eq!(kana("Tokyo"), "ときょ");
+        assert_eq!(kana("kyouto"), "きょうと");
+        assert_eq!(kana("tōkyō"), "とうきょう");
+        // kunrei-shiki
+        assert_eq!(kana("si"), kana("shi"));
+        assert_eq!(kana("tu"), kana("tsu"));
+        assert_eq!(kana("ra-men"), "らーめん");
+    }
+
+    #[test]
+    fn romaji_n() {
+        assert_eq!(kana("konnichiwa"), "こんにちわ");
+        assert_eq!(kana("hon"), "ほん");
+        assert_eq!(kana("kan'i"), "かんい");
+        assert_eq!(kana("kani"), "かに");
+        assert_eq!(kana("shimbun"), "しんぶん");
+    }
+
+    #[test]
+    fn romaji_small_tsu() {
+        assert_eq!(kana("kitte"), "きって");
+        assert_eq!(kana("matcha"), "まっちゃ");
+        assert_eq!(kana("zasshi"), "ざっし");
+    }
+
+    #[test]
+    fn not_romaji() {
+        assert_eq!(romaji_to_hiragana("すし"), None);
+        assert_eq!(romaji_to_hiragana("hello world"), None);
+        // no syllable starts with q
+        assert_eq!(romaji_to_hiragana("qa"), None);
+    }
+
+    #[test]
+    fn scripts() {
+        assert_eq!(to_katakana("すし"), "スシ");
+        assert_eq!(to_hiragana("ラーメン"), "らーめん");
+        assert!(is_kana('ー'));
+        assert!(!is_kana('漢'));
+    }
+
+    #[test]
+    fn conjugations() {
+        for (word, base) in [
+            ("たべました", "たべる"),
+            ("のみたい", "のむ"),
+            ("かかない", "かく"),
+            ("はなして", "はなす"),
+            ("よんだ", "よむ"),
+            ("いった", "いく"),
+            ("べんきょうした", "べんきょうする"),
+            ("きた", "くる"),
+            ("たかかった", "たかい"),
+            // two conjugations deep
+            ("たべられない", "たべる"),
+        ] {
+            assert!(deinflect(word).contains(&base.to_owned()), "{word}");
+        }
+        assert!(deinflect("た").is_empty());
+    }
+
+    #[test]
+    fn variants_of_romaji() {
+        let v = variants("tabemashita");
+        assert_eq!(v[..2], ["たべました", "タベマシタ"]);
+        assert!(v.contains(&"たべる".to_owned()));
+        assert!(v.len() <= LIMIT);
+        assert!(variants("apple").iter().all(|w| !w.is_ascii()));
+        assert!(variants("漢字").is_empty());
+    }
+}
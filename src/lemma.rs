@@ -0,0 +1,351 @@
+//! Base forms of inflected English words, after WordNet's morphy.
+//!
+//! Irregular forms come from a table, the rest from suffix rules.
+
+/// (inflected, base) in the spirit of the WordNet exception lists
+const IRREGULAR: &[(&str, &str)] = &[
+    // nouns
+    ("alumni", "alumnus"),
+    ("analyses", "analysis"),
+    ("bacteria", "bacterium"),
+    ("cacti", "cactus"),
+    ("calves", "calf"),
+    ("children", "child"),
+    ("criteria", "criterion"),
+    ("crises", "crisis"),
+    ("data", "datum"),
+    ("dice", "die"),
+    ("elves", "elf"),
+    ("feet", "foot"),
+    ("fungi", "fungus"),
+    ("geese", "goose"),
+    ("halves", "half"),
+    ("hypotheses", "hypothesis"),
+    ("indices", "index"),
+    ("knives", "knife"),
+    ("leaves", "leaf"),
+    ("lice", "louse"),
+    ("lives", "life"),
+    ("loaves", "loaf"),
+    ("matrices", "matrix"),
+    ("media", "medium"),
+    ("mice", "mouse"),
+    ("nuclei", "nucleus"),
+    ("oases", "oasis"),
+    ("oxen", "ox"),
+    ("people", "person"),
+    ("phenomena", "phenomenon"),
+    ("radii", "radius"),
+    ("selves", "self"),
+    ("sheaves", "sheaf"),
+    ("shelves", "shelf"),
+    ("stimuli", "stimulus"),
+    ("teeth", "tooth"),
+    ("theses", "thesis"),
+    ("thieves", "thief"),
+    ("vertices", "vertex"),
+    ("wives", "wife"),
+    ("wolves", "wolf"),
+    // verbs
+    ("am", "be"),
+    ("are", "be"),
+    ("ate", "eat"),
+    ("is", "be"),
+    ("was", "be"),
+    ("were", "be"),
+    ("been", "be"),
+    ("awoke", "awake"),
+    ("awoken", "awake"),
+    ("bore", "bear"),
+    ("borne", "bear"),
+    ("beaten", "beat"),
+    ("became", "become"),
+    ("began", "begin"),
+    ("begun", "begin"),
+    ("bent", "bend"),
+    ("bit", "bite"),
+    ("bitten", "bite"),
+    ("bled", "bleed"),
+    ("blew", "blow"),
+    ("blown", "blow"),
+    ("broke", "break"),
+    ("broken", "break"),
+    ("bred", "breed"),
+    ("brought", "bring"),
+    ("built", "build"),
+    ("burnt", "burn"),
+    ("bought", "buy"),
+    ("caught", "catch"),
+    ("chose", "choose"),
+    ("chosen", "choose"),
+    ("came", "come"),
+    ("crept", "creep"),
+    ("dealt", "deal"),
+    ("did", "do"),
+    ("does", "do"),
+    ("done", "do"),
+    ("drew", "draw"),
+    ("drawn", "draw"),
+    ("dreamt", "dream"),
+    ("drank", "drink"),
+    ("drunk", "drink"),
+    ("drove", "drive"),
+    ("driven", "drive"),
+    ("dug", "dig"),
+    ("eaten", "eat"),
+    ("fell", "fall"),
+    ("fallen", "fall"),
+    ("fed", "feed"),
+    ("felt", "feel"),
+    ("fought", "fight"),
+    ("found", "find"),
+    ("fled", "flee"),
+    ("flew", "fly"),
+    ("flown", "fly"),
+    ("forbade", "forbid"),
+    ("forbidden", "forbid"),
+    ("forgot", "forget"),
+    ("forgotten", "forget"),
+    ("forgave", "forgive"),
+    ("forgiven", "forgive"),
+    ("froze", "freeze"),
+    ("frozen", "freeze"),
+    ("got", "get"),
+    ("gotten", "get"),
+    ("gave", "give"),
+    ("given", "give"),
+    ("went", "go"),
+    ("gone", "go"),
+    ("goes", "go"),
+    ("ground", "grind"),
+    ("grew", "grow"),
+    ("grown", "grow"),
+    ("hung", "hang"),
+    ("had", "have"),
+    ("has", "have"),
+    ("heard", "hear"),
+    ("hid", "hide"),
+    ("hidden", "hide"),
+    ("held", "hold"),
+    ("kept", "keep"),
+    ("knelt", "kneel"),
+    ("knew", "know"),
+    ("known", "know"),
+    ("laid", "lay"),
+    ("led", "lead"),
+    ("leapt", "leap"),
+    ("learnt", "learn"),
+    ("left", "leave"),
+    ("lent", "lend"),
+    ("lain", "lie"),
+    ("lay", "lie"),
+    ("lit", "light"),
+    ("lost", "lose"),
+    ("made", "make"),
+    ("meant", "mean"),
+    ("met", "meet"),
+    ("mistook", "mistake"),
+    ("mistaken", "mistake"),
+    ("paid", "pay"),
+    ("rode", "ride"),
+    ("ridden", "ride"),
+    ("rang", "ring"),
+    ("rung", "ring"),
+    ("rose", "rise"),
+    ("risen", "rise"),
+    ("ran", "run"),
+    ("said", "say"),
+    ("saw", "see"),
+    ("seen", "see"),
+    ("sought", "seek"),
+    ("sold", "sell"),
+    ("sent", "send"),
+    ("shook", "shake"),
+    ("shaken", "shake"),
+    ("shone", "shine"),
+    ("shot", "shoot"),
+    ("shown", "show"),
+    ("shrank", "shrink"),
+    ("shrunk", "shrink"),
+    ("sang", "sing"),
+    ("sung", "sing"),
+    ("sank", "sink"),
+    ("sunk", "sink"),
+    ("sat", "sit"),
+    ("slept", "sleep"),
+    ("slid", "slide"),
+    ("spoke", "speak"),
+    ("spoken", "speak"),
+    ("sped", "speed"),
+    ("spent", "spend"),
+    ("spun", "spin"),
+    ("spat", "spit"),
+    ("stood", "stand"),
+    ("stole", "steal"),
+    ("stolen", "steal"),
+    ("stuck", "stick"),
+    ("stung", "sting"),
+    ("stank", "stink"),
+    ("strode", "stride"),
+    ("struck", "strike"),
+    ("strove", "strive"),
+    ("striven", "strive"),
+    ("swore", "swear"),
+    ("sworn", "swear"),
+    ("swept", "sweep"),
+    ("swam", "swim"),
+    ("swum", "swim"),
+    ("swung", "swing"),
+    ("took", "take"),
+    ("taken", "take"),
+    ("taught", "teach"),
+    ("tore", "tear"),
+    ("torn", "tear"),
+    ("told", "tell"),
+    ("thought", "think"),
+    ("threw", "throw"),
+    ("thrown", "throw"),
+    ("understood", "understand"),
+    ("woke", "wake"),
+    ("woken", "wake"),
+    ("wore", "wear"),
+    ("worn", "wear"),
+    ("wove", "weave"),
+    ("woven", "weave"),
+    ("wept", "weep"),
+    ("won", "win"),
+    ("wound", "wind"),
+    ("wrote", "write"),
+    ("written", "write"),
+    // adjectives and adverbs
+    ("better", "good"),
+    ("best", "good"),
+    ("worse", "bad"),
+    ("worst", "bad"),
+    ("further", "far"),
+    ("furthest", "far"),
+    ("farther", "far"),
+    ("farthest", "far"),
+    ("less", "little"),
+    ("least", "little"),
+    ("more", "much"),
+    ("most", "much"),
+    ("elder", "old"),
+    ("eldest", "old"),
+];
+
+/// (suffix, replacement) of nouns, verbs and adjectives, as in morphy
+const SUFFIXES: &[(&str, &str)] = &[
+    // nouns
+    ("ses", "s"),
+    ("xes", "x"),
+    ("zes", "z"),
+    ("ches", "ch"),
+    ("shes", "sh"),
+    ("men", "man"),
+    ("ies", "y"),
+    ("s", ""),
+    // verbs
+    ("es", "e"),
+    ("es", ""),
+    ("ed", "e"),
+    ("ed", ""),
+    ("ing", "e"),
+    ("ing", ""),
+    // adjectives
+    ("er", ""),
+    ("est", ""),
+    ("er", "e"),
+    ("est", "e"),
+    ("ier", "y"),
+    ("iest", "y"),
+];
+
+/// possible base forms of `word`, most likely first, `word` itself excluded
+pub fn candidates(word: &str) -> Vec<String> {
+    let lower = word.to_lowercase();
+    let mut v: Vec<String> = IRREGULAR
+        .iter()
+        .filter(|(inflected, _)| *inflected == lower)
+        .map(|(_, base)| base.to_string())
+        .collect();
+
+    for (suffix, replacement) in SUFFIXES {
+        let Some(stem) = lower.strip_suffix(suffix) else {
+            continue;
+        };
+        // a stem needs a vowel, "s" of "bus" is not a suffix
+        if stem.chars().count() < 2 || !stem.chars().any(|c| "aeiouy".contains(c)) {
+            continue;
+        }
+        let base = format!("{stem}{replacement}");
+        // running -> runn -> run, stopped -> stopp -> stop
+        if replacement.is_empty() && matches!(*suffix, "ing" | "ed" | "er" | "est") {
+            let mut chars = stem.chars().rev();
+            if let (Some(a), Some(b)) = (chars.next(), chars.next()) {
+                if a == b && !"aeiouslz".contains(a) {
+                    v.push(stem[..stem.len() - a.len_utf8()].to_owned());
+                }
+            }
+        }
+        v.push(base);
+    }
+
+    let mut seen = vec![lower];
+    v.retain(|w| {
+        if seen.contains(w) {
+            false
+        } else {
+            seen.push(w.clone());
+            true
+        }
+    });
+    v
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn irregular() {
+        assert_eq!(candidates("geese")[0], "goose");
+        assert_eq!(candidates("Went")[0], "go");
+        // "lay" is both a past form and a base form
+        assert_eq!(candidates("lay")[0], "lie");
+    }
+
+    #[test]
+    fn suffixes() {
+        assert!(candidates("boxes").contains(&"box".to_owned()));
+        assert!(candidates("churches").contains(&"church".to_owned()));
+        assert!(candidates("cities").contains(&"city".to_owned()));
+        assert!(candidates("making").contains(&"make".to_owned()));
+        assert!(candidates("walked").contains(&"walk".to_owned()));
+        assert!(candidates("happiest").contains(&"happy".to_owned()));
+    }
+
+    #[test]
+    fn doubled_consonant() {
+        assert!(candidates("running").contains(&"run".to_owned()));
+        assert!(candidates("stopped").contains(&"stop".to_owned()));
+        assert!(candidates("bigger").contains(&"big".to_owned()));
+        // not undoubled
+        assert!(!candidates("falling").contains(&"fal".to_owned()));
+        assert!(candidates("falling").contains(&"fall".to_owned()));
+    }
+
+    #[test]
+    fn no_stem() {
+        // a stem needs a vowel and two letters
+        assert!(candidates("tsks").is_empty());
+        assert!(candidates("as").is_empty());
+        assert!(candidates("is").iter().eq(["be"]));
+        // the word itself and duplicates are left out
+        let v = candidates("uses");
+        assert!(!v.contains(&"uses".to_owned()));
+        let mut dedup = v.clone();
+        dedup.dedup();
+        assert_eq!(v, dedup);
+    }
+}
@@ -0,0 +1,62 @@
+//! Dictionaries kept loaded by the long-running servers.
+use crate::headword_index::{HeadwordIndex, Mask};
+use crate::load_dict;
+use crate::utils::dict_ids;
+use crate::T;
+use anyhow::Result;
+use log::*;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub struct Database {
+    /// unique short name, see `dict_ids`
+    pub id: String,
+    pub dict: Mutex<Box<dyn T>>,
+    /// the dictionary in the headword index
+    pub mask: Mask,
+}
+
+impl Database {
+    /// "did you mean" candidates
+    pub fn suggestions(&self, index: &HeadwordIndex, word: &str) -> Vec<String> {
+        let mut v = crate::suggest::candidates(index, word, self.mask);
+        match self.dict.lock().unwrap().suggestions(word) {
+            Ok(s) => v.extend(s),
+            Err(e) => error!("{}: {e}", self.id),
+        }
+        v
+    }
+}
+
+pub struct Library {
+    pub databases: Vec<Database>,
+    pub index: HeadwordIndex,
+}
+
+/// load the dictionaries and their headword index
+pub fn load_library() -> Result<Library> {
+    let dicts = load_dict();
+    let paths: Vec<PathBuf> = dicts.iter().map(|d| d.path().to_path_buf()).collect();
+    let path_refs: Vec<&Path> = paths.iter().map(PathBuf::as_path).collect();
+    let files: Vec<Vec<PathBuf>> = dicts.iter().map(|d| d.files()).collect();
+    let index = HeadwordIndex::open(&files, |i| match dicts[i].headwords() {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{:?}: {e}", paths[i]);
+            Vec::new()
+        }
+    })?;
+
+    let ids = dict_ids(path_refs.iter().copied());
+    let databases = dicts
+        .into_iter()
+        .zip(ids)
+        .enumerate()
+        .map(|(i, (dict, id))| Database {
+            id,
+            dict: Mutex::new(dict),
+            mask: Mask::Dict(i),
+        })
+        .collect();
+    Ok(Library { databases, index })
+}
@@ -1,8 +1,18 @@
 #![feature(async_closure)]
 
+use crate::cache::Catalog;
+use crate::config::config;
+use crate::dict_client::DictClient;
 use crate::fsrs::sqlite_history::add_history;
+use crate::fulltext::FullText;
+use crate::headword_index::{HeadwordIndex, Mask};
+use crate::hunspell::Hunspell;
 use crate::mdict_wrapper::Mdict;
+use crate::plugin::Plugin;
 use crate::stardict::StarDict;
+use crate::stardict::{Entry, EntryWrapper};
+use crate::view::View;
+use crate::zim::Zim;
 use anyhow::anyhow;
 use anyhow::Context;
 use anyhow::Result;
@@ -10,46 +20,118 @@ use chrono::prelude::*;
 use env_logger::Target;
 use log::*;
 use rayon::prelude::*;
+use regex::RegexBuilder;
 use shadow_rs::shadow;
 use std::ffi::OsStr;
-use std::fs::create_dir;
 use std::fs::OpenOptions;
 use std::path::Path;
 use std::sync::mpsc::channel;
+use std::sync::OnceLock;
 use std::{
     env::{self},
-    fs::File,
+    fs::{self, File},
     io::Write,
     path::PathBuf,
-    process::Command,
+    process::ExitCode,
 };
+use tempfile::TempDir;
 use walkdir::WalkDir;
 
 mod anki;
+mod cache;
+mod config;
+mod dict_client;
+mod dict_server;
+mod dicts;
 mod fsrs;
+mod fulltext;
+mod headword_index;
+mod hunspell;
+mod ja;
+mod lemma;
+mod library;
 mod mdict_wrapper;
+mod normalize;
+mod phrase;
+mod plugin;
+mod render;
+mod server;
 mod spaced_repetition;
 mod stardict;
+mod suggest;
 mod utils;
+mod view;
+mod zh;
+mod zim;
 
 shadow!(build);
 
+/// exits with 1 when the word is not found, 2 on other errors
 #[tokio::main]
-async fn main() -> Result<()> {
-    let word = env::args().nth(1).unwrap();
-    match &*word {
-        "--help" => {
-            println!("https://github.com/lengyijun/mdict-cli-rs/");
-            Ok(())
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) if e.is::<NotFound>() => {
+            eprintln!("{e}");
+            ExitCode::from(1)
         }
-        "--version" => {
-            println!("{}", build::VERSION); //print version const
-            Ok(())
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            ExitCode::from(2)
         }
+    }
+}
+
+async fn run() -> Result<()> {
+    // --text, --format, --config and --group can be anywhere
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    if let Some(i) = args.iter().position(|a| a == "--config") {
+        args.remove(i);
+        if i == args.len() {
+            return Err(anyhow!("missing config file"));
+        }
+        let path = PathBuf::from(args.remove(i));
+        if !path.is_file() {
+            return Err(anyhow!("no config file {:?}", path));
+        }
+        config::set_config_path(path);
+    }
+    // these work with a broken config
+    if args.iter().any(|a| a == "--help") {
+        println!("https://github.com/lengyijun/mdict-cli-rs/");
+        return Ok(());
+    }
+    if args.iter().any(|a| a == "--version") {
+        println!("{}", build::VERSION); //print version const
+        return Ok(());
+    }
+    config::load()?;
+    if let Some(i) = args.iter().position(|a| a == "--group") {
+        args.remove(i);
+        if i == args.len() {
+            return Err(anyhow!("missing group"));
+        }
+        dicts::set_group(args.remove(i))?;
+    }
+    let mut view = View::Viewer;
+    if let Some(i) = args.iter().position(|a| a == "--text") {
+        args.remove(i);
+        view = View::Text;
+    }
+    if let Some(i) = args.iter().position(|a| a == "--format") {
+        args.remove(i);
+        if i == args.len() {
+            return Err(anyhow!("missing format"));
+        }
+        view = View::parse(&args.remove(i))?;
+    }
+    view::set(view);
+    let word = args.first().cloned().context("missing word, see --help")?;
+    match &*word {
         "--list-dict" | "--list-dicts" => {
             let v = load_dict();
             if v.is_empty() {
-                println!("no dictionary found in {:?}", dictionary_dir());
+                println!("no dictionary found in {:?}", dictionary_dirs());
                 return Ok(());
             }
             for dict in v {
@@ -57,12 +139,46 @@ async fn main() -> Result<()> {
             }
             Ok(())
         }
+        "--dicts" => dicts::dicts(&args[1..], find_dicts()),
         "--show-path" => {
-            println!("dictionary dir            {:?}", dictionary_dir());
+            let config = config();
+            println!("config file               {:?}", config::config_path());
+            for dir in dictionary_dirs() {
+                println!("dictionary dir            {:?}", dir);
+            }
             println!("history database          {:?}", db_path());
             println!("log dir                   {:?}", log_dir());
+            println!("cache dir                 {:?}", cache::cache_dir());
+            println!("viewer                    {:?}", view::viewer());
+            println!("normalize                 {:?}", normalize::steps_setting());
+            println!("server                    {:?}", config.server.bind);
+            println!("dict server               {:?}", config.server.dict_bind);
+            println!("order                     {:?}", config.order);
+            println!("disabled                  {:?}", config.disabled);
+            println!("groups                    {:?}", config.groups.keys());
+            println!("group                     {:?}", dicts::group());
             Ok(())
         }
+        "--serve" => {
+            env_logger::Builder::from_default_env()
+                .filter_level(log::LevelFilter::Info) // Set the minimum log level
+                .init();
+            let addr = args
+                .get(1)
+                .cloned()
+                .unwrap_or_else(|| config().server.bind.clone());
+            server::serve(&addr).await
+        }
+        "--serve-dict" => {
+            env_logger::Builder::from_default_env()
+                .filter_level(log::LevelFilter::Info) // Set the minimum log level
+                .init();
+            let addr = args
+                .get(1)
+                .cloned()
+                .unwrap_or_else(|| config().server.dict_bind.clone());
+            dict_server::serve_dict(&addr).await
+        }
         "anki" => {
             let local: DateTime<Local> = Local::now();
             let log_path = log_dir().join(format!("log.{}", local.to_rfc3339()));
@@ -83,48 +199,434 @@ async fn main() -> Result<()> {
             anki::anki().await?;
             Ok(())
         }
+        "--match" | "--regex" => {
+            env_logger::Builder::from_default_env()
+                .filter_level(log::LevelFilter::Info) // Set the minimum log level
+                .init();
+            let pattern = args.get(1).cloned().context("missing pattern")?;
+            let re = if word == "--match" {
+                utils::wildcard_to_regex(&pattern)
+            } else {
+                pattern.clone()
+            };
+            let re = RegexBuilder::new(&re)
+                .case_insensitive(true)
+                .build()
+                .with_context(|| format!("bad pattern {pattern}"))?;
+
+            // the dictionaries are loaded again by `query`
+            let word = {
+                let dicts = load_dict();
+                let index = open_index(&dicts)?;
+                let entries: Vec<EntryWrapper> = index
+                    .regex(&re, MATCH_LIMIT)
+                    .into_iter()
+                    .map(|(word, i)| EntryWrapper {
+                        dict_name: dicts[i].name(),
+                        entry: Entry { word },
+                    })
+                    .collect();
+                if entries.is_empty() {
+                    println!("no headword matches {pattern}");
+                    return Ok(());
+                }
+                if entries.len() >= MATCH_LIMIT {
+                    println!("only the first {MATCH_LIMIT} headwords are listed");
+                }
+                let Some(i) = suggest::select(&entries) else {
+                    return Ok(());
+                };
+                entries[i].entry.word.clone()
+            };
+            look_up(word, view).await
+        }
+        "--reverse" => {
+            env_logger::Builder::from_default_env()
+                .filter_level(log::LevelFilter::Info) // Set the minimum log level
+                .init();
+            let word = args.get(1).cloned().context("missing word")?;
+            let temp_dir = tempfile::Builder::new().prefix(&word).tempdir()?;
+            let found = reverse_query(&word, temp_dir.path()).await?;
+            show(&found, view, temp_dir)
+        }
+        "--phrase" => {
+            env_logger::Builder::from_default_env()
+                .filter_level(log::LevelFilter::Info) // Set the minimum log level
+                .init();
+            let sentence = args[1..].join(" ");
+            if sentence.trim().is_empty() {
+                return Err(anyhow!("missing phrase"));
+            }
+            let temp_dir = tempfile::Builder::new().prefix("phrase-").tempdir()?;
+            let found = phrase_query(&sentence, temp_dir.path())?;
+            show(&found, view, temp_dir)
+        }
+        "--search" => {
+            env_logger::Builder::from_default_env()
+                .filter_level(log::LevelFilter::Info) // Set the minimum log level
+                .init();
+            let terms = args[1..].join(" ");
+            if terms.trim().is_empty() {
+                return Err(anyhow!("missing search terms"));
+            }
+
+            let full_text = FullText::open().await?;
+            full_text.update(&load_dict()).await?;
+            let hits = full_text
+                .search(&terms, ("\x1b[1m", "\x1b[0m"), SEARCH_LIMIT)
+                .await?;
+            if hits.is_empty() {
+                println!("no definition contains {terms}");
+                return Ok(());
+            }
+            let Some(i) = suggest::select(&hits) else {
+                return Ok(());
+            };
+            look_up(hits[i].word.clone(), view).await
+        }
         _ => {
             env_logger::Builder::from_default_env()
                 .filter_level(log::LevelFilter::Info) // Set the minimum log level
                 .init();
+            look_up(word, view).await
+        }
+    }
+}
 
-            let temp_dir = tempfile::Builder::new().prefix(&word).tempdir()?;
-            let index_html = query(&word, temp_dir.path())?;
-            add_history(&word).await?;
-            let _ = Command::new("carbonyl").arg(index_html).status()?;
-            Ok(())
+/// how many headwords `--match` and `--regex` list
+const MATCH_LIMIT: usize = 200;
+
+/// how many headwords `--search` lists
+const SEARCH_LIMIT: u32 = 50;
+
+/// the most words of a phrase `--phrase` looks up together
+const PHRASE_LIMIT: usize = 5;
+
+/// how many headwords `--reverse` shows
+const REVERSE_LIMIT: u32 = 30;
+
+/// query `word` and show the result
+async fn look_up(word: String, view: View) -> Result<()> {
+    let temp_dir = tempfile::Builder::new().prefix(&word).tempdir()?;
+    let found = match query(&word, temp_dir.path()) {
+        Ok(found) => found,
+        Err(e) => {
+            let Some(not_found) = e.downcast_ref::<NotFound>() else {
+                return Err(e);
+            };
+            if not_found.suggestions.is_empty() || view.is_format() {
+                return Err(e);
+            }
+            println!("{word} not found, did you mean:");
+            // still not found when none is picked
+            let Some(i) = suggest::select(&not_found.suggestions) else {
+                return Err(e);
+            };
+            query(&not_found.suggestions[i], temp_dir.path())?
         }
+    };
+    if !view.is_format() {
+        add_history(&found.word).await?;
+    }
+    show(&found, view, temp_dir)
+}
+
+/// show `found`, written in `temp_dir`, which is left behind when the viewer needs it,
+/// see `View::keeps_files`
+fn show(found: &Found, view: View, temp_dir: TempDir) -> Result<()> {
+    view::show(found, view)?;
+    if view.keeps_files() {
+        let _ = temp_dir.into_path();
     }
+    Ok(())
 }
 
-fn query(word: &str, base_dir: &Path) -> Result<PathBuf> {
+/// what `query` found
+pub struct Found {
+    /// the word looked up, its base form when the word itself is not in any dictionary
+    pub word: String,
+    pub index_html: PathBuf,
+    pub pages: Vec<Page>,
+    /// shown above the results, e.g. the base form looked up instead
+    pub note: Option<String>,
+}
+
+/// the result of one dictionary
+pub struct Page {
+    /// name of the dictionary
+    pub dictionary: String,
+    /// path to the dictionary
+    pub path: PathBuf,
+    pub headword: String,
+    /// where `T::lookup` wrote the result
+    pub dir: PathBuf,
+}
+
+fn query(word: &str, base_dir: &Path) -> Result<Found> {
     info!("{word}");
-    let (sender, receiver) = channel();
 
-    load_dict()
+    // keep the dictionaries around for fallbacks and suggestions
+    let mut dicts = load_dict();
+    if let Some(found) = find(&mut dicts, word, base_dir)? {
+        return Ok(found);
+    }
+
+    let mut candidates = match open_index(&dicts) {
+        Ok(index) => suggest::candidates(&index, word, Mask::All),
+        Err(e) => {
+            error!("{e}");
+            Vec::new()
+        }
+    };
+    candidates.par_extend(
+        dicts
+            .into_par_iter()
+            .flat_map_iter(|dict| dict.suggestions(word).unwrap_or_default()),
+    );
+    Err(NotFound {
+        word: word.to_owned(),
+        suggestions: suggest::suggest(word, candidates, suggest::LIMIT),
+    }
+    .into())
+}
+
+/// the results of `word`, or of the first of its fallbacks found
+fn find(dicts: &mut Vec<Box<dyn T>>, word: &str, base_dir: &Path) -> Result<Option<Found>> {
+    let pages = lookup_all(dicts, word, base_dir);
+    if !pages.is_empty() {
+        let buttons = tabs(&pages);
+        let open = default_tab(dicts, &pages);
+        return write_result_page(base_dir, word, &buttons, open, pages, None).map(Some);
+    }
+
+    for lemma in fallbacks(word) {
+        let pages = lookup_all(dicts, &lemma, base_dir);
+        if !pages.is_empty() {
+            info!("{word} -> {lemma}");
+            let note = format!("{word} → {lemma}");
+            let buttons = tabs(&pages);
+            let open = default_tab(dicts, &pages);
+            return write_result_page(base_dir, &lemma, &buttons, open, pages, Some(note))
+                .map(Some);
+        }
+    }
+    Ok(None)
+}
+
+/// words to try when `word` is not found: the other Chinese script, the stems of
+/// Hunspell dictionaries, English base forms, then Japanese kana and dictionary forms
+fn fallbacks(word: &str) -> Vec<String> {
+    // parsed once, a phrase or a server misses many words
+    static HUNSPELL: OnceLock<Vec<Hunspell>> = OnceLock::new();
+    let hunspell = HUNSPELL.get_or_init(|| {
+        dictionary_dirs()
+            .iter()
+            .flat_map(|d| hunspell::load(d))
+            .collect()
+    });
+    let mut v = zh::variants(word);
+    for lemma in hunspell::stems(hunspell, word)
+        .into_iter()
+        .chain(lemma::candidates(word))
+        .chain(ja::variants(word))
+    {
+        if !v.contains(&lemma) {
+            v.push(lemma);
+        }
+    }
+    v
+}
+
+/// look up `word` in every dictionary, a page for each result in the order of `dicts`
+fn lookup_all(dicts: &mut Vec<Box<dyn T>>, word: &str, base_dir: &Path) -> Vec<Page> {
+    let (sender, receiver) = channel();
+    *dicts = std::mem::take(dicts)
         .into_par_iter()
-        .for_each_with(sender, |s, dict| {
+        .enumerate()
+        .map_with(sender, |s, (i, dict)| {
             if let Ok(p) = dict.lookup(word, base_dir) {
-                s.send(format!(
-                    r#"<button onclick="changeIframeSrc('{}/index.html', this)">{}</button>"#,
-                    p.file_name().unwrap().to_str().unwrap(),
-                    dict.name(),
+                s.send((
+                    i,
+                    Page {
+                        dictionary: dict.name().to_owned(),
+                        path: dict.path().to_path_buf(),
+                        headword: word.to_owned(),
+                        dir: p,
+                    },
                 ))
                 .unwrap();
             }
-        });
+            dict
+        })
+        .collect();
+    let mut pages: Vec<(usize, Page)> = receiver.iter().collect();
+    pages.sort_by_key(|(i, _)| *i);
+    pages.into_iter().map(|(_, page)| page).collect()
+}
 
-    let buttons: Vec<_> = receiver.iter().collect();
+/// the page of the first dictionary shown when the result page opens, see `T::open`
+fn default_tab(dicts: &[Box<dyn T>], pages: &[Page]) -> usize {
+    pages
+        .iter()
+        .position(|page| dicts.iter().any(|d| d.open() && d.path() == page.path))
+        .unwrap_or(0)
+}
+
+/// look up the headwords whose definitions have `word`, a tab for each of them
+async fn reverse_query(word: &str, base_dir: &Path) -> Result<Found> {
+    info!("reverse {word}");
+    let dicts = load_dict();
+    let full_text = FullText::open().await?;
+    full_text.update(&dicts).await?;
+
+    let mut buttons = Vec::new();
+    let mut pages = Vec::new();
+    for (headword, path) in full_text.reverse(word, REVERSE_LIMIT).await? {
+        let Some(dict) = dicts.iter().find(|d| d.path() == path) else {
+            continue;
+        };
+        if let Ok(p) = dict.lookup(&headword, base_dir) {
+            buttons.push(tab(&p, format!("{headword} ({})", dict.name())));
+            pages.push(Page {
+                dictionary: dict.name().to_owned(),
+                path,
+                headword,
+                dir: p,
+            });
+        }
+    }
 
     if buttons.is_empty() {
-        error!("{word} not found");
-        return Err(anyhow!("{word} not found"));
+        return Err(NotFound {
+            word: word.to_owned(),
+            suggestions: Vec::new(),
+        }
+        .into());
     }
+    let open = default_tab(&dicts, &pages);
+    write_result_page(base_dir, word, &buttons, open, pages, None)
+}
+
+/// look up the longest phrases of `text` which are headwords, then its other words,
+/// a tab for each with the results of all dictionaries inside
+fn phrase_query(text: &str, base_dir: &Path) -> Result<Found> {
+    info!("phrase {text}");
+    let tokens = phrase::tokens(text);
+    let mut dicts = load_dict();
+    let index = open_index(&dicts)?;
+
+    let mut buttons = Vec::new();
+    let mut pages = Vec::new();
+    let segments = phrase::segments(&tokens, PHRASE_LIMIT, |s| index.contains(s));
+    for (i, segment) in segments.into_iter().enumerate() {
+        // a token may have a `/` or be `..`
+        let dir = utils::create_sub_dir(base_dir, &format!("segment-{i}"))?;
+        if let Some(found) = find(&mut dicts, &segment, &dir)? {
+            let name = if found.word == segment {
+                segment
+            } else {
+                format!("{segment} → {}", found.word)
+            };
+            buttons.push(tab(&dir, name));
+            pages.extend(found.pages);
+        }
+    }
+
+    if buttons.is_empty() {
+        return Err(NotFound {
+            word: text.to_owned(),
+            suggestions: Vec::new(),
+        }
+        .into());
+    }
+    // the tabs are pages of tabs, `pages` are the results inside
+    write_result_page(base_dir, text, &buttons, 0, pages, None)
+}
+
+/// the (url, name) of the result written in `dir`
+fn tab(dir: &Path, name: String) -> (String, String) {
+    (
+        format!(
+            "{}/index.html",
+            utils::percent_encode(dir.file_name().unwrap().to_str().unwrap())
+        ),
+        name,
+    )
+}
 
-    let buttons_str = buttons.join("\n");
+/// a tab for each page, named by the dictionary
+fn tabs(pages: &[Page]) -> Vec<(String, String)> {
+    pages
+        .iter()
+        .map(|page| tab(&page.dir, page.dictionary.clone()))
+        .collect()
+}
 
+fn write_result_page(
+    base_dir: &Path,
+    word: &str,
+    buttons: &[(String, String)],
+    open: usize,
+    pages: Vec<Page>,
+    note: Option<String>,
+) -> Result<Found> {
     let index_html = base_dir.join("index.html");
-    let html = format!(
+    let html = result_page(buttons, open, note.as_deref());
+    File::create(&index_html)?.write_all(html.as_bytes())?;
+
+    Ok(Found {
+        word: word.to_owned(),
+        index_html,
+        pages,
+        note,
+    })
+}
+
+/// the headword index of `dicts`, built if they changed
+fn open_index(dicts: &[Box<dyn T>]) -> Result<HeadwordIndex> {
+    let files: Vec<Vec<PathBuf>> = dicts.iter().map(|d| d.files()).collect();
+    HeadwordIndex::open(&files, |i| dicts[i].headwords().unwrap_or_default())
+}
+
+/// no dictionary has the word
+#[derive(Debug)]
+pub struct NotFound {
+    pub word: String,
+    /// closest headwords, best first
+    pub suggestions: Vec<String>,
+}
+
+impl std::fmt::Display for NotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} not found", self.word)
+    }
+}
+
+impl std::error::Error for NotFound {}
+
+/// the page with a button for each (url, dictionary name), both unescaped, on the left and the result on the right,
+/// the one of button `open` first, `note` is shown above the buttons
+fn result_page(buttons: &[(String, String)], open: usize, note: Option<&str>) -> String {
+    let note = note
+        .map(|note| format!(r#"<p class="note">{}</p>"#, utils::escape_html(note)))
+        .unwrap_or_default();
+    let src = buttons
+        .get(open)
+        .map(|(url, _)| utils::escape_html(url))
+        .unwrap_or_default();
+    let buttons_str = buttons
+        .iter()
+        .enumerate()
+        .map(|(i, (url, name))| {
+            let class = if i == open { r#" class="active""# } else { "" };
+            // the url is a javascript string in an attribute
+            let url = utils::escape_html(&utils::escape_js(url));
+            let name = utils::escape_html(name);
+            format!(r#"<button{class} onclick="changeIframeSrc('{url}', this)">{name}</button>"#)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!(
         r#"
 <!DOCTYPE html>
 <html lang="en">
@@ -177,6 +679,11 @@ fn query(word: &str, base_dir: &Path) -> Result<PathBuf> {
       background-color: gray;
     }}
 
+    .sidebar .note {{
+      margin: 0 0 10px;
+      color: #555;
+    }}
+
     /* 右边的 iframe */
     .content {{
       flex-grow: 1;
@@ -194,12 +701,13 @@ fn query(word: &str, base_dir: &Path) -> Result<PathBuf> {
 
   <!-- 左边的可滚动按钮列 -->
   <div class="sidebar">
+   {note}
    {buttons_str}
   </div>
 
   <!-- 右边显示 iframe -->
   <div class="content">
-    <iframe id="myIframe" src="page1.html"></iframe>
+    <iframe id="myIframe" src="{src}"></iframe>
   </div>
 
   <script>
@@ -227,16 +735,24 @@ fn query(word: &str, base_dir: &Path) -> Result<PathBuf> {
 </body>
 </html>
 "#
-    );
-    File::create(&index_html)?.write_all(html.as_bytes())?;
+    )
+}
 
-    Ok(index_html)
+/// the dirs searched for dictionaries, `~/.local/share/mdict-cli-rs` by default
+fn dictionary_dirs() -> Vec<PathBuf> {
+    let dirs = &config().dictionary_dirs;
+    if dirs.is_empty() {
+        vec![dirs::data_local_dir().unwrap().join("mdict-cli-rs")]
+    } else {
+        dirs.iter().map(|d| config::expand(d)).collect()
+    }
 }
 
+/// the first dictionary dir, which keeps the indexes and the history
 fn dictionary_dir() -> PathBuf {
-    let path = dirs::data_local_dir().unwrap().join("mdict-cli-rs");
+    let path = dictionary_dirs().swap_remove(0);
     if !path.exists() {
-        create_dir(&path)
+        fs::create_dir_all(&path)
             .with_context(|| format!("Failed to create directory {:?}", path))
             .unwrap();
     }
@@ -244,26 +760,40 @@ fn dictionary_dir() -> PathBuf {
 }
 
 fn db_path() -> PathBuf {
-    dictionary_dir().join("history.db")
+    match &config().db_path {
+        Some(path) => config::expand(path),
+        None => dictionary_dir().join("history.db"),
+    }
 }
 
 fn log_dir() -> PathBuf {
-    let path = dirs::cache_dir().unwrap().join("mdict-cli-rs");
+    let path = match &config().log_dir {
+        Some(path) => config::expand(path),
+        None => dirs::cache_dir().unwrap().join("mdict-cli-rs"),
+    };
     if !path.exists() {
-        create_dir(&path)
+        fs::create_dir_all(&path)
             .with_context(|| format!("Failed to create directory {:?}", path))
             .unwrap();
     }
     path
 }
 
-// load mdict or stardict
+// load mdict, stardict, zim, plugins or dictd servers, in the order of the config
 fn load_dict() -> Vec<Box<dyn T>> {
-    let d = dictionary_dir();
+    dicts::arrange(find_dicts())
+}
 
+/// every dictionary in the dictionary dirs, the disabled ones included,
+/// their names are cached in the catalog
+fn find_dicts() -> Vec<Box<dyn T>> {
     let mut v: Vec<Box<dyn T>> = Vec::new();
+    let mut catalog = Catalog::open();
 
-    for entry in WalkDir::new(d).follow_links(true) {
+    for entry in dictionary_dirs()
+        .into_iter()
+        .flat_map(|d| WalkDir::new(d).follow_links(true).sort_by_file_name())
+    {
         let Ok(entry) = entry else { continue };
         if entry.file_type().is_dir() {
             continue;
@@ -271,24 +801,50 @@ fn load_dict() -> Vec<Box<dyn T>> {
         if let Some(extension) = entry.path().extension().and_then(OsStr::to_str) {
             match extension {
                 "mdx" => {
-                    v.push(Box::new(Mdict {
-                        mdx_path: entry.path().to_path_buf(),
-                    }));
+                    v.push(Box::new(Mdict::new(entry.path().to_path_buf())));
                 }
                 "dz" => {
-                    if let Ok(stardict) = StarDict::dz(entry.path()) {
+                    let path = entry.path();
+                    if let Ok(stardict) = catalog
+                        .name(&stardict::sibling(path, "ifo"), || StarDict::bookname(path))
+                        .and_then(|name| StarDict::dz(path, name))
+                    {
                         v.push(Box::new(stardict));
                     }
                 }
                 "dict" => {
-                    if let Ok(stardict) = StarDict::dict(entry.path()) {
+                    let path = entry.path();
+                    if let Ok(stardict) = catalog
+                        .name(&stardict::sibling(path, "ifo"), || StarDict::bookname(path))
+                        .and_then(|name| StarDict::dict(path, name))
+                    {
                         v.push(Box::new(stardict));
                     }
                 }
+                "zim" => match Zim::new(entry.path()) {
+                    Ok(mut zim) => match catalog.name(entry.path(), || zim.title()) {
+                        Ok(name) => {
+                            zim.set_name(name);
+                            v.push(Box::new(zim));
+                        }
+                        Err(e) => error!("{e}"),
+                    },
+                    Err(e) => error!("{e}"),
+                },
+                "dictplugin" => match Plugin::new(entry.path()) {
+                    Ok(plugin) => v.push(Box::new(plugin)),
+                    Err(e) => error!("{e}"),
+                },
+                "dictd" => match DictClient::new(entry.path()) {
+                    Ok(client) => v.push(Box::new(client)),
+                    Err(e) => error!("{e}"),
+                },
                 _ => {}
             }
         }
     }
+
+    catalog.save();
     v
 }
 
@@ -299,6 +855,40 @@ trait T: Send {
     /// path to dict
     fn path(&self) -> &Path;
 
+    /// the files it's read from, the headword index is rebuilt when one changes
+    fn files(&self) -> Vec<PathBuf> {
+        vec![self.path().to_path_buf()]
+    }
+
     /// write the result in @return/index.html
     fn lookup(&self, word: &str, base_dir: &Path) -> Result<PathBuf>;
+
+    /// the html of `word`, without resources
+    fn definition(&self, word: &str) -> Result<String>;
+
+    /// a file referenced by the html, e.g. css, js or images
+    fn resource(&self, path: &str) -> Result<Vec<u8>> {
+        Err(anyhow!("{path}: {} has no resources", self.name()))
+    }
+
+    /// all headwords, empty if the dictionary can't list them
+    fn headwords(&self) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    /// whether the definitions are indexed for full-text search
+    fn full_text(&self) -> bool {
+        false
+    }
+
+    /// headwords close to `word`, for dictionaries which can't list their headwords,
+    /// the others are covered by the headword index
+    fn suggestions(&self, _word: &str) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    /// whether its result is shown when the result page opens
+    fn open(&self) -> bool {
+        false
+    }
 }
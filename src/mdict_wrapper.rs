@@ -1,13 +1,19 @@
+use crate::normalize::normalize;
 use crate::utils::create_sub_dir;
 use crate::utils::groom_name;
+use crate::utils::safe_relative_path;
+use crate::view;
 use crate::T;
 use anyhow::anyhow;
 use anyhow::{Context, Result};
 use ego_tree::NodeRef;
 use log::*;
-use mdict::{KeyMaker, MDictBuilder};
+use mdict::{KeyMaker, MDict, MDictBuilder};
 use scraper::{Html, Node};
+use std::borrow::Cow;
+use std::cell::RefCell;
 use std::path::Path;
+use std::sync::Mutex;
 use std::{
     collections::HashSet,
     fs::{self, File},
@@ -18,19 +24,42 @@ use std::{
 struct MyKeyMaker;
 
 impl KeyMaker for MyKeyMaker {
-    fn make(&self, key: &std::borrow::Cow<str>, _resource: bool) -> String {
+    fn make(&self, key: &std::borrow::Cow<str>, resource: bool) -> String {
         fn strip_punctuation(w: &str) -> String {
             w.to_lowercase()
                 .chars()
                 .filter(|c| !c.is_ascii_punctuation() && !c.is_whitespace())
                 .collect()
         }
-        strip_punctuation(key)
+        if resource {
+            strip_punctuation(key)
+        } else {
+            normalize(key)
+        }
     }
 }
 
 pub struct Mdict {
     pub mdx_path: PathBuf,
+    /// parsed on first use
+    mdx: Mutex<Option<MDict<MyKeyMaker>>>,
+}
+
+impl Mdict {
+    pub fn new(mdx_path: PathBuf) -> Self {
+        Self {
+            mdx_path,
+            mdx: Mutex::new(None),
+        }
+    }
+
+    fn with_mdx<R>(&self, f: impl FnOnce(&mut MDict<MyKeyMaker>) -> Result<R>) -> Result<R> {
+        let mut mdx = self.mdx.lock().unwrap();
+        if mdx.is_none() {
+            *mdx = Some(MDictBuilder::new(&self.mdx_path).build_with_key_maker(MyKeyMaker)?);
+        }
+        f(mdx.as_mut().unwrap())
+    }
 }
 
 impl T for Mdict {
@@ -42,12 +71,48 @@ impl T for Mdict {
         &self.mdx_path
     }
 
-    fn lookup(&self, word: &str, base_dir: &Path) -> Result<PathBuf> {
-        let mut mdx = MDictBuilder::new(&self.mdx_path).build_with_key_maker(MyKeyMaker)?;
-        let definition = mdx.lookup(word)?;
-        let Some(definition) = definition else {
-            return Result::Err(anyhow!("not found"));
+    fn definition(&self, word: &str) -> Result<String> {
+        self.with_mdx(|mdx| match mdx.lookup(word)? {
+            Some(definition) => Ok(definition.definition),
+            None => Result::Err(anyhow!("not found")),
+        })
+    }
+
+    fn headwords(&self) -> Result<Vec<String>> {
+        // mdict doesn't expose its keys, collect them while they are made
+        let keys = RefCell::new(Vec::new());
+        let key_maker = |key: &Cow<str>, resource: bool| {
+            if !resource {
+                keys.borrow_mut().push(key.to_string());
+            }
+            MyKeyMaker.make(key, resource)
         };
+        drop(MDictBuilder::new(&self.mdx_path).build_with_key_maker(key_maker)?);
+        Ok(keys.into_inner())
+    }
+
+    fn full_text(&self) -> bool {
+        true
+    }
+
+    /// files next to the mdx first, then the mdd
+    fn resource(&self, path: &str) -> Result<Vec<u8>> {
+        let path = path.trim_start_matches('/');
+        let rel = safe_relative_path(path).with_context(|| format!("bad path {path}"))?;
+        let p = self.mdx_path.parent().unwrap().join(rel);
+        if p.is_file() {
+            return Ok(fs::read(p)?);
+        }
+
+        let key = "\\".to_owned() + &path.replace('/', "\\");
+        self.with_mdx(|mdx| match mdx.get_resource(&key)? {
+            Some(x) => Ok(x.into_owned()),
+            None => Result::Err(anyhow!("{path} not found")),
+        })
+    }
+
+    fn lookup(&self, word: &str, base_dir: &Path) -> Result<PathBuf> {
+        let definition = self.definition(word)?;
 
         let base_dir = create_sub_dir(
             base_dir,
@@ -55,47 +120,27 @@ impl T for Mdict {
         )?;
 
         let index_html = base_dir.join("index.html");
-        File::create(&index_html)?.write_all(definition.definition.as_bytes())?;
-
-        let mdd_path = self.mdx_path.with_extension("mdd");
-        let mut mdd = MDictBuilder::new(&mdd_path).build_with_key_maker(MyKeyMaker);
+        File::create(&index_html)?.write_all(definition.as_bytes())?;
 
         let mut resources: HashSet<String> = HashSet::new();
-        let dom = Html::parse_document(&definition.definition);
-        dfs(dom.tree.root(), &mut resources);
+        if view::shows_resources() {
+            let dom = Html::parse_document(&definition);
+            dfs(dom.tree.root(), &mut resources);
+        }
         for resource in resources {
-            let p = {
-                let mut p = self.mdx_path.clone();
-                p.pop();
-                p.push(&resource);
-                p
-            };
-            if p.exists() {
-                let dest = base_dir.join(resource);
-                fs::copy(p, dest)?;
+            let Some(rel) = safe_relative_path(resource.trim_start_matches('/')) else {
                 continue;
-            }
-
-            let mut resource = resource;
-            if !resource.starts_with('/') {
-                resource = "/".to_owned() + &resource;
-            }
-            let word = &resource.replace('/', "\\");
-            match mdd.as_mut().map(|mdd| mdd.lookup(word)) {
-                Ok(Ok(Some(x))) => {
-                    let dest = base_dir.join(&resource[1..]);
+            };
+            match self.resource(&resource) {
+                Ok(data) => {
+                    let dest = base_dir.join(rel);
+                    fs::create_dir_all(dest.parent().unwrap())?;
                     File::create(&dest)
                         .with_context(|| format!("fail to create {:?}", dest))?
-                        .write_all(x.definition.as_bytes())?;
-                }
-                Ok(Ok(None)) => {
-                    error!("{} failed to load {resource}", self.name());
-                }
-                Ok(Err(e)) => {
-                    error!("{} failed to load {resource} {e}", self.name());
+                        .write_all(&data)?;
                 }
                 Err(e) => {
-                    error!("{:?} not exist; {e}", mdd_path);
+                    error!("{} failed to load {resource} {e}", self.name());
                 }
             }
         }
@@ -0,0 +1,184 @@
+//! Headword normalization shared by all dictionaries.
+//!
+//! The steps are set by `MDICT_CLI_NORMALIZE` or `normalize` of the config,
+//! a comma separated list applied in order, default `nfkc,lowercase,diacritics,punctuation,strip`.
+use crate::config::config;
+use log::*;
+use std::env;
+use std::sync::OnceLock;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+pub const ENV: &str = "MDICT_CLI_NORMALIZE";
+
+const DEFAULT: &str = "nfkc,lowercase,diacritics,punctuation,strip";
+
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Nfc,
+    Nfd,
+    Nfkc,
+    Nfkd,
+    Lowercase,
+    /// café -> cafe
+    Diacritics,
+    /// full-width ascii -> ascii
+    Width,
+    /// unify hyphen, apostrophe and quote variants
+    Punctuation,
+    /// remove ascii punctuation and whitespace
+    Strip,
+}
+
+impl Step {
+    fn parse(s: &str) -> Option<Step> {
+        Some(match s.trim().to_lowercase().as_str() {
+            "nfc" => Step::Nfc,
+            "nfd" => Step::Nfd,
+            "nfkc" => Step::Nfkc,
+            "nfkd" => Step::Nfkd,
+            "lowercase" => Step::Lowercase,
+            "diacritics" => Step::Diacritics,
+            "width" => Step::Width,
+            "punctuation" => Step::Punctuation,
+            "strip" => Step::Strip,
+            _ => return None,
+        })
+    }
+
+    fn apply(self, s: &str) -> String {
+        match self {
+            Step::Nfc => s.nfc().collect(),
+            Step::Nfd => s.nfd().collect(),
+            Step::Nfkc => s.nfkc().collect(),
+            Step::Nfkd => s.nfkd().collect(),
+            Step::Lowercase => s.to_lowercase(),
+            Step::Diacritics => s
+                .nfd()
+                // only the combining diacritical marks, kana voicing marks are kept
+                .filter(|&c| !(is_combining_mark(c) && ('\u{300}'..='\u{36f}').contains(&c)))
+                .flat_map(fold_letter)
+                .collect::<String>()
+                .nfc()
+                .collect(),
+            Step::Width => s
+                .chars()
+                .map(|c| match c {
+                    '\u{ff01}'..='\u{ff5e}' => char::from_u32(c as u32 - 0xfee0).unwrap(),
+                    '\u{3000}' => ' ',
+                    c => c,
+                })
+                .collect(),
+            Step::Punctuation => {
+                s.chars()
+                    .map(|c| match c {
+                        '\u{2010}'..='\u{2015}' | '\u{2212}' | '\u{fe63}' | '\u{ff0d}' => '-',
+                        '\u{2018}' | '\u{2019}' | '\u{201b}' | '\u{2032}' | '\u{02bc}'
+                        | '\u{ff07}' => '\'',
+                        '\u{201c}' | '\u{201d}' | '\u{201f}' | '\u{2033}' | '\u{ff02}' => '"',
+                        c => c,
+                    })
+                    .collect()
+            }
+            Step::Strip => s
+                .chars()
+                .filter(|c| !c.is_ascii_punctuation() && !c.is_whitespace())
+                .collect(),
+        }
+    }
+}
+
+/// letters without a decomposition
+fn fold_letter(c: char) -> Vec<char> {
+    match c {
+        'ß' => vec!['s', 's'],
+        'æ' => vec!['a', 'e'],
+        'Æ' => vec!['A', 'E'],
+        'œ' => vec!['o', 'e'],
+        'Œ' => vec!['O', 'E'],
+        'ø' => vec!['o'],
+        'Ø' => vec!['O'],
+        'ł' => vec!['l'],
+        'Ł' => vec!['L'],
+        'đ' => vec!['d'],
+        'Đ' => vec!['D'],
+        'ı' => vec!['i'],
+        c => vec![c],
+    }
+}
+
+/// the comma separated steps
+pub fn steps_setting() -> String {
+    env::var(ENV)
+        .ok()
+        .or_else(|| config().normalize.clone())
+        .unwrap_or_else(|| DEFAULT.to_owned())
+}
+
+fn parse_steps(setting: &str) -> Vec<Step> {
+    setting
+        .split(',')
+        .filter(|s| !s.trim().is_empty())
+        .filter_map(|name| {
+            let step = Step::parse(name);
+            if step.is_none() {
+                warn!("{ENV}: unknown step {name}");
+            }
+            step
+        })
+        .collect()
+}
+
+fn steps() -> &'static [Step] {
+    static STEPS: OnceLock<Vec<Step>> = OnceLock::new();
+    STEPS.get_or_init(|| parse_steps(&steps_setting()))
+}
+
+fn apply(steps: &[Step], s: &str) -> String {
+    steps.iter().fold(s.to_owned(), |s, step| step.apply(&s))
+}
+
+/// the form of a headword or a query used to match them
+pub fn normalize(s: &str) -> String {
+    apply(steps(), s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn default(s: &str) -> String {
+        apply(&parse_steps(DEFAULT), s)
+    }
+
+    #[test]
+    fn pipeline() {
+        for s in ["café", "CAFÉ", "ｃａｆｅ", "Cafe\u{301}", " café. "] {
+            assert_eq!(default(s), "cafe", "{s}");
+        }
+        assert_eq!(default("Straße"), "strasse");
+        assert_eq!(default("Œuvre"), "oeuvre");
+        assert_eq!(default("rock ’n’ roll"), "rocknroll");
+        assert_eq!(default("e-mail"), default("e\u{2010}mail"));
+        assert_eq!(default("ﬁ"), "fi");
+    }
+
+    #[test]
+    fn kept() {
+        // kana voicing marks aren't diacritics
+        assert_eq!(default("が"), "が");
+        assert_eq!(default("ｶﾞ"), "ガ");
+        assert_eq!(default("國家"), "國家");
+        assert_eq!(default("ёж"), "еж");
+    }
+
+    #[test]
+    fn each_step() {
+        assert_eq!(apply(&parse_steps(""), "Café!"), "Café!");
+        assert_eq!(apply(&parse_steps(" Lowercase , bogus"), "Café!"), "café!");
+        assert_eq!(apply(&parse_steps("width"), "ＡＢ　１"), "AB 1");
+        assert_eq!(apply(&parse_steps("punctuation"), "‘a’ “b”"), "'a' \"b\"");
+        assert_eq!(apply(&parse_steps("nfd"), "é").chars().count(), 2);
+        assert_eq!(apply(&parse_steps("nfd,nfc"), "é").chars().count(), 1);
+    }
+}
@@ -0,0 +1,115 @@
+//! Splitting a phrase or a sentence into the words looked up by `--phrase`.
+//!
+//! Words are separated by spaces, Chinese is segmented by jieba.
+use crate::fulltext::jieba;
+use crate::zh::is_han;
+
+/// the words of `text`, punctuation around them removed
+pub fn tokens(text: &str) -> Vec<String> {
+    let mut v = Vec::new();
+    for word in text.split_whitespace() {
+        let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+        if word.chars().any(is_han) {
+            v.extend(
+                jieba()
+                    .cut(word, true)
+                    .into_iter()
+                    .filter(|w| w.chars().any(char::is_alphanumeric))
+                    .map(str::to_owned),
+            );
+        } else if !word.is_empty() {
+            v.push(word.to_owned());
+        }
+    }
+    v
+}
+
+/// `tokens` grouped into the phrases looked up, the longest of at most `limit` tokens
+/// which `is_headword` first, else a single token
+pub fn segments(
+    tokens: &[String],
+    limit: usize,
+    is_headword: impl Fn(&str) -> bool,
+) -> Vec<String> {
+    let mut v = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let n = (2..=limit.min(tokens.len() - i))
+            .rev()
+            .find(|&n| is_headword(&join(&tokens[i..i + n])))
+            .unwrap_or(1);
+        v.push(join(&tokens[i..i + n]));
+        i += n;
+    }
+    v
+}
+
+/// the phrase made of `tokens`, with no space between Chinese words
+pub fn join(tokens: &[String]) -> String {
+    let mut s = String::new();
+    for t in tokens {
+        let han = s.chars().last().is_some_and(is_han) && t.chars().next().is_some_and(is_han);
+        if !s.is_empty() && !han {
+            s.push(' ');
+        }
+        s.push_str(t);
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADWORDS: &[&str] = &["give up", "ice cream", "give up on", "冰淇淋", "up"];
+
+    fn split(text: &str) -> Vec<String> {
+        segments(&tokens(text), 5, |s| HEADWORDS.contains(&s))
+    }
+
+    #[test]
+    fn punctuation() {
+        assert_eq!(
+            tokens("“Well,” she said... (twice)!"),
+            ["Well", "she", "said", "twice"]
+        );
+        assert_eq!(tokens("rock 'n' roll"), ["rock", "n", "roll"]);
+        assert_eq!(tokens("don't -- stop"), ["don't", "stop"]);
+        assert!(tokens(" ... ").is_empty());
+    }
+
+    #[test]
+    fn longest_match() {
+        assert_eq!(
+            split("she gave up ice cream"),
+            ["she", "gave", "up", "ice cream"]
+        );
+        assert_eq!(split("give up on it"), ["give up on", "it"]);
+        assert_eq!(split("give up, ice cream!"), ["give up", "ice cream"]);
+    }
+
+    #[test]
+    fn single_words() {
+        assert_eq!(split("an ice"), ["an", "ice"]);
+        assert_eq!(split("cream ice"), ["cream", "ice"]);
+        assert!(split("").is_empty());
+        // at most `limit` tokens
+        let tokens = tokens("give up on");
+        assert_eq!(
+            segments(&tokens, 2, |s| HEADWORDS.contains(&s)),
+            ["give up", "on"]
+        );
+    }
+
+    #[test]
+    fn chinese() {
+        let t = tokens("我喜欢冰淇淋。");
+        assert!(t.iter().all(|w| !w.contains('。')));
+        assert_eq!(join(&t), "我喜欢冰淇淋");
+        assert!(split("我喜欢冰淇淋").contains(&"冰淇淋".to_owned()));
+        assert_eq!(
+            join(&["吃".to_owned(), "ice".to_owned(), "冰".to_owned()]),
+            "吃 ice 冰"
+        );
+    }
+}
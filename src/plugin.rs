@@ -0,0 +1,359 @@
+//! External dictionaries speaking JSON lines over stdio.
+//!
+//! `foo.dictplugin` in the dictionary dir declares the executable:
+//! `{"name": "foo", "command": "./foo.py", "args": []}`
+//!
+//! The process is spawned on the first lookup and kept alive. For every lookup it
+//! receives `{"lookup": "word", "normalized": "word"}`, the latter as other dictionaries
+//! match headwords (see `normalize`), and answers one line
+//! `{"html": "...", "resources": [{"path": "a.css", "base64": "..."}]}`.
+//! A resource may also be given as `{"path": "a.png", "file": "/abs/path/a.png"}`.
+//! `"html": null` means not found.
+//! The resources of the last answers are kept for `T::resource`, so `--serve` finds them too.
+//! A plugin which doesn't answer in time is killed and spawned again on the next lookup.
+use crate::normalize::normalize;
+use crate::utils::create_sub_dir;
+use crate::utils::groom_name;
+use crate::utils::safe_relative_path;
+use crate::view;
+use crate::T;
+use anyhow::{anyhow, Context, Result};
+use base64::prelude::*;
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io::{prelude::*, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+/// how long a plugin may take to answer
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// how many answers the resources are kept of
+const KEPT_ANSWERS: usize = 32;
+
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    name: Option<String>,
+    command: String,
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Response {
+    html: Option<String>,
+    #[serde(default)]
+    resources: Vec<Resource>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Resource {
+    path: String,
+    base64: Option<String>,
+    file: Option<PathBuf>,
+}
+
+struct Process {
+    child: Child,
+    stdin: ChildStdin,
+    /// the lines of stdout, read by a thread so they can be waited for with a timeout
+    stdout: Receiver<std::io::Result<String>>,
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// (relative path, content)
+type Resources = Vec<(PathBuf, Vec<u8>)>;
+
+pub struct Plugin {
+    manifest_path: PathBuf,
+    name: String,
+    manifest: Manifest,
+    process: Mutex<Option<Process>>,
+    timeout: Duration,
+    /// the resources of the last `KEPT_ANSWERS` answers by path, the latest first
+    resources: Mutex<VecDeque<HashMap<PathBuf, Vec<u8>>>>,
+}
+
+impl Plugin {
+    pub fn new(manifest_path: &Path) -> Result<Plugin> {
+        let s = fs::read_to_string(manifest_path)
+            .with_context(|| format!("Failed to open plugin manifest {:?}", manifest_path))?;
+        let manifest: Manifest = serde_json::from_str(&s)
+            .with_context(|| format!("Failed to parse plugin manifest {:?}", manifest_path))?;
+        let name = match &manifest.name {
+            Some(name) => name.clone(),
+            None => manifest_path
+                .file_stem()
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_owned(),
+        };
+        Ok(Plugin {
+            manifest_path: manifest_path.to_path_buf(),
+            name,
+            manifest,
+            process: Mutex::new(None),
+            timeout: TIMEOUT,
+            resources: Mutex::new(VecDeque::new()),
+        })
+    }
+
+    fn spawn(&self) -> Result<Process> {
+        let dir = self.manifest_path.parent().unwrap();
+        // relative commands are relative to the manifest
+        let command = if self.manifest.command.contains('/') {
+            dir.join(&self.manifest.command)
+        } else {
+            PathBuf::from(&self.manifest.command)
+        };
+        let mut child = Command::new(&command)
+            .args(&self.manifest.args)
+            .current_dir(dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to spawn plugin {:?}", command))?;
+        let stdin = child.stdin.take().unwrap();
+        let mut reader = BufReader::new(child.stdout.take().unwrap());
+        let (sender, stdout) = mpsc::channel();
+        // ends when the process is killed and stdout is closed
+        thread::spawn(move || loop {
+            let mut line = String::new();
+            let res = reader.read_line(&mut line).map(|_| line);
+            let end = !matches!(&res, Ok(line) if !line.is_empty());
+            if sender.send(res).is_err() || end {
+                break;
+            }
+        });
+        Ok(Process {
+            child,
+            stdin,
+            stdout,
+        })
+    }
+
+    fn request(&self, word: &str) -> Result<Response> {
+        let mut process = self.process.lock().unwrap();
+        if process.is_none() {
+            *process = Some(self.spawn()?);
+        }
+        let p = process.as_mut().unwrap();
+
+        let res = (|| -> Result<Response> {
+            writeln!(
+                p.stdin,
+                "{}",
+                serde_json::json!({ "lookup": word, "normalized": normalize(word) })
+            )?;
+            p.stdin.flush()?;
+            let line = match p.stdout.recv_timeout(self.timeout) {
+                Ok(line) => line?,
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(anyhow!(
+                        "plugin {} didn't answer in {:?}",
+                        self.name,
+                        self.timeout
+                    ))
+                }
+                Err(RecvTimeoutError::Disconnected) => String::new(),
+            };
+            if line.is_empty() {
+                return Err(anyhow!("plugin {} exited", self.name));
+            }
+            Ok(serde_json::from_str(&line)?)
+        })();
+        if res.is_err() {
+            // killed, respawn on the next lookup
+            *process = None;
+        }
+        res
+    }
+
+    /// the html of `word` and its resources, which are all read before any is used
+    fn answer(&self, word: &str) -> Result<(String, Resources)> {
+        let response = self.request(word)?;
+        if let Some(e) = response.error {
+            return Err(anyhow!("plugin {}: {e}", self.name));
+        }
+        let Some(html) = response.html else {
+            return Result::Err(anyhow!("not found"));
+        };
+
+        let mut resources = Vec::new();
+        for resource in response.resources {
+            let Some(rel) = safe_relative_path(&resource.path) else {
+                return Err(anyhow!("plugin {}: bad path {}", self.name, resource.path));
+            };
+            let data = match (resource.base64, resource.file) {
+                (Some(data), _) => BASE64_STANDARD.decode(data).with_context(|| {
+                    format!("plugin {}: bad base64 of {}", self.name, resource.path)
+                })?,
+                (None, Some(file)) => {
+                    fs::read(&file).with_context(|| format!("Failed to open {:?}", file))?
+                }
+                (None, None) => {
+                    return Err(anyhow!(
+                        "plugin {}: empty resource {}",
+                        self.name,
+                        resource.path
+                    ))
+                }
+            };
+            resources.push((rel.to_path_buf(), data));
+        }
+
+        if !resources.is_empty() {
+            let mut kept = self.resources.lock().unwrap();
+            kept.push_front(resources.iter().cloned().collect());
+            kept.truncate(KEPT_ANSWERS);
+        }
+        Ok((html, resources))
+    }
+}
+
+impl T for Plugin {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn path(&self) -> &Path {
+        &self.manifest_path
+    }
+
+    fn definition(&self, word: &str) -> Result<String> {
+        Ok(self.answer(word)?.0)
+    }
+
+    fn resource(&self, path: &str) -> Result<Vec<u8>> {
+        safe_relative_path(path)
+            .and_then(|rel| {
+                let kept = self.resources.lock().unwrap();
+                kept.iter().find_map(|answer| answer.get(rel).cloned())
+            })
+            .with_context(|| format!("plugin {}: no resource {path}", self.name))
+    }
+
+    fn lookup(&self, word: &str, base_dir: &Path) -> Result<PathBuf> {
+        let (html, resources) = self.answer(word)?;
+
+        let base_dir = create_sub_dir(base_dir, &groom_name(&self.name))?;
+
+        let index_html = base_dir.join("index.html");
+        File::create(&index_html)?.write_all(html.as_bytes())?;
+
+        if !view::shows_resources() {
+            return Ok(base_dir);
+        }
+        for (rel, data) in resources {
+            let dest = base_dir.join(rel);
+            fs::create_dir_all(dest.parent().unwrap())?;
+            File::create(&dest)
+                .with_context(|| format!("fail to create {:?}", dest))?
+                .write_all(&data)?;
+        }
+
+        Ok(base_dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// knows "apple" with a stylesheet, "pear" with a broken resource, hangs on "hang",
+    /// and echoes the request of "echo"
+    const SCRIPT: &str = r#"while read -r line; do
+  case "$line" in
+    *'"lookup":"apple"'*) echo '{"html": "<p>apple</p>", "resources": [{"path": "css/a.css", "base64": "cCB7fQ=="}]}' ;;
+    *'"lookup":"pear"'*) echo '{"html": "<p>pear</p>", "resources": [{"path": "../b.css", "base64": ""}]}' ;;
+    *'"lookup":"fail"'*) echo '{"html": null, "error": "broken"}' ;;
+    *'"lookup":"echo'*) echo "{\"html\": $(printf %s "$line" | sed 's/"/\\"/g; s/^/"/; s/$/"/')}" ;;
+    *'"lookup":"hang"'*) ;;
+    *) echo '{"html": null}' ;;
+  esac
+done"#;
+
+    fn plugin(dir: &Path) -> Plugin {
+        let manifest = dir.join("stub.dictplugin");
+        let json = serde_json::json!({ "name": "Stub", "command": "sh", "args": ["-c", SCRIPT] });
+        fs::write(&manifest, json.to_string()).unwrap();
+        let mut plugin = Plugin::new(&manifest).unwrap();
+        plugin.timeout = Duration::from_millis(500);
+        plugin
+    }
+
+    #[test]
+    fn lookup() {
+        let dir = tempfile::tempdir().unwrap();
+        let plugin = plugin(dir.path());
+        assert_eq!(plugin.name(), "Stub");
+
+        let out = tempfile::tempdir().unwrap();
+        let result = plugin.lookup("apple", out.path()).unwrap();
+        assert_eq!(
+            fs::read_to_string(result.join("index.html")).unwrap(),
+            "<p>apple</p>"
+        );
+        assert_eq!(fs::read(result.join("css/a.css")).unwrap(), b"p {}");
+        assert_eq!(plugin.resource("css/a.css").unwrap(), b"p {}");
+        assert!(plugin.resource("../css/a.css").is_err());
+        assert!(plugin.resource("b.css").is_err());
+    }
+
+    #[test]
+    fn request() {
+        let dir = tempfile::tempdir().unwrap();
+        let plugin = plugin(dir.path());
+        assert_eq!(
+            plugin.definition("echo Ünïcode").unwrap(),
+            r#"{"lookup":"echo Ünïcode","normalized":"echounicode"}"#
+        );
+    }
+
+    #[test]
+    fn not_found() {
+        let dir = tempfile::tempdir().unwrap();
+        let plugin = plugin(dir.path());
+        let out = tempfile::tempdir().unwrap();
+        assert!(plugin.lookup("banana", out.path()).is_err());
+        let e = plugin.lookup("fail", out.path()).unwrap_err();
+        assert_eq!(e.to_string(), "plugin Stub: broken");
+        // a bad resource writes nothing
+        assert!(plugin.lookup("pear", out.path()).is_err());
+        assert_eq!(fs::read_dir(out.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let plugin = plugin(dir.path());
+        let e = plugin.definition("hang").unwrap_err();
+        assert!(e.to_string().contains("didn't answer"), "{e}");
+        // spawned again
+        assert_eq!(plugin.definition("apple").unwrap(), "<p>apple</p>");
+    }
+
+    #[test]
+    fn kept_resources() {
+        let dir = tempfile::tempdir().unwrap();
+        let plugin = plugin(dir.path());
+        for _ in 0..KEPT_ANSWERS + 5 {
+            plugin.definition("apple").unwrap();
+        }
+        assert_eq!(plugin.resources.lock().unwrap().len(), KEPT_ANSWERS);
+        assert!(plugin.resource("css/a.css").is_ok());
+    }
+}
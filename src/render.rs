@@ -0,0 +1,318 @@
+//! Render dictionary html as plain text.
+use ego_tree::NodeRef;
+use scraper::{Html, Node};
+use textwrap::Options;
+
+const BLOCKS: [&str; 22] = [
+    "address",
+    "article",
+    "blockquote",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "ol",
+    "p",
+    "section",
+    "ul",
+];
+
+const SKIPPED: [&str; 5] = ["head", "script", "style", "title", "template"];
+
+pub fn html_to_text(html: &str) -> String {
+    let dom = Html::parse_document(html);
+    let mut w = Writer::default();
+    walk(dom.tree.root(), &mut w, false);
+    tidy(&w.out)
+}
+
+/// `html` styled by ANSI escape codes, lists indented and lines wrapped to `width` columns
+pub fn html_to_ansi(html: &str, width: usize) -> String {
+    let dom = Html::parse_document(html);
+    let mut w = Writer {
+        ansi: true,
+        ..Writer::default()
+    };
+    walk(dom.tree.root(), &mut w, false);
+    wrap(&tidy(&w.out), width)
+}
+
+const BOLD: &str = "1";
+const ITALIC: &str = "3";
+const CYAN: &str = "36";
+
+#[derive(Default)]
+struct Writer {
+    out: String,
+    ansi: bool,
+    /// the escape codes of the elements around the text
+    styles: Vec<&'static str>,
+    /// the next number of each indented element around, `None` but for ordered lists
+    lists: Vec<Option<usize>>,
+}
+
+impl Writer {
+    fn at_line_start(&self) -> bool {
+        self.out.is_empty() || self.out.ends_with('\n')
+    }
+
+    /// indent lists, quotes and definitions, only when styled
+    fn indent(&mut self) {
+        if self.ansi && self.at_line_start() {
+            self.out.push_str(&"  ".repeat(self.lists.len()));
+        }
+    }
+
+    fn push_text(&mut self, s: &str) {
+        self.indent();
+        if self.ansi && !self.styles.is_empty() {
+            self.out
+                .push_str(&format!("\x1b[{}m{s}\x1b[0m", self.styles.join(";")));
+        } else {
+            self.out.push_str(s);
+        }
+    }
+}
+
+/// the escape code of an element, bold headwords and colored phonetics
+fn style(e: &scraper::node::Element) -> Option<&'static str> {
+    let class = e.attr("class").unwrap_or_default().to_lowercase();
+    if ["phon", "pron", "ipa"].iter().any(|c| class.contains(c)) {
+        return Some(CYAN);
+    }
+    match e.name() {
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "b" | "strong" => Some(BOLD),
+        "i" | "em" => Some(ITALIC),
+        _ if ["hw", "headword"].iter().any(|c| class.contains(c)) => Some(BOLD),
+        _ => None,
+    }
+}
+
+fn walk(node: NodeRef<Node>, w: &mut Writer, pre: bool) {
+    match node.value() {
+        Node::Text(text) => {
+            if pre {
+                w.push_text(text);
+            } else {
+                for (i, word) in text.split_whitespace().enumerate() {
+                    if (i > 0 || text.starts_with(char::is_whitespace))
+                        && !w.at_line_start()
+                        && !w.out.ends_with(' ')
+                    {
+                        w.out.push(' ');
+                    }
+                    w.push_text(word);
+                }
+                if text.ends_with(char::is_whitespace) && !w.out.ends_with(['\n', ' ']) {
+                    w.out.push(' ');
+                }
+            }
+        }
+        Node::Element(e) => {
+            let name = e.name();
+            if SKIPPED.contains(&name) {
+                return;
+            }
+            match name {
+                "br" => w.out.push('\n'),
+                "li" => {
+                    newline(&mut w.out);
+                    // the bullet is at the level of the list
+                    let list = w.lists.pop();
+                    w.indent();
+                    w.lists.extend(list);
+                    match w.lists.last_mut() {
+                        Some(Some(n)) if w.ansi => {
+                            w.out.push_str(&format!("{n}. "));
+                            *n += 1;
+                        }
+                        _ => w.out.push_str("• "),
+                    }
+                }
+                "td" | "th" if !w.out.ends_with('\n') => w.out.push('\t'),
+                "img" => {
+                    if let Some(alt) = e.attr("alt").filter(|alt| !alt.is_empty()) {
+                        w.push_text(&format!("[{alt}]"));
+                    }
+                }
+                _ => {}
+            }
+            let block = BLOCKS.contains(&name) || matches!(name, "pre" | "tr" | "table");
+            if block {
+                newline(&mut w.out);
+            }
+            let list = matches!(name, "ul" | "ol" | "dd" | "blockquote");
+            if list {
+                w.lists.push((name == "ol").then_some(1));
+            }
+            let code = w.ansi.then(|| style(e)).flatten();
+            if let Some(code) = code {
+                w.styles.push(code);
+            }
+            for child in node.children() {
+                walk(child, w, pre || name == "pre");
+            }
+            if code.is_some() {
+                w.styles.pop();
+            }
+            if list {
+                w.lists.pop();
+            }
+            if block || name == "li" {
+                newline(&mut w.out);
+            }
+        }
+        _ => {
+            for child in node.children() {
+                walk(child, w, pre);
+            }
+        }
+    }
+}
+
+fn newline(out: &mut String) {
+    while out.ends_with(' ') {
+        out.pop();
+    }
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+/// trim lines and squeeze blank lines
+fn tidy(s: &str) -> String {
+    let mut out = String::new();
+    let mut blank = 0;
+    for line in s.lines() {
+        let line = line.trim_end();
+        if line.trim().is_empty() {
+            blank += 1;
+            continue;
+        }
+        if blank > 0 && !out.is_empty() {
+            out.push('\n');
+        }
+        blank = 0;
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+/// wrap long lines, the continuation lines indented past the bullet or the number
+fn wrap(s: &str, width: usize) -> String {
+    let mut out = String::new();
+    for line in s.lines() {
+        if line.is_empty() {
+            out.push('\n');
+            continue;
+        }
+        let text = line.trim_start();
+        let lead = &line[..line.len() - text.len()];
+        // in columns, a full-width space of CJK is two
+        let mut indent = " ".repeat(textwrap::core::display_width(lead));
+        let bullet = text.split_once(' ').map(|(b, _)| b).filter(|b| {
+            *b == "•"
+                || b.strip_suffix('.')
+                    .is_some_and(|n| n.parse::<usize>().is_ok())
+        });
+        let first = match bullet {
+            Some(b) => {
+                let first = format!("{lead}{b} ");
+                indent.push_str(&" ".repeat(b.chars().count() + 1));
+                first
+            }
+            None => lead.to_owned(),
+        };
+        let text = bullet.map_or(text, |b| &text[b.len() + 1..]);
+        let options = Options::new(width)
+            .initial_indent(&first)
+            .subsequent_indent(&indent);
+        for l in textwrap::wrap(text, options) {
+            out.push_str(&l);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entities() {
+        assert_eq!(
+            html_to_text("<p>fish &amp; chips &lt;n&gt; caf&eacute;&nbsp;au&#32;lait</p>"),
+            "fish & chips <n> café au lait\n"
+        );
+    }
+
+    #[test]
+    fn blocks() {
+        let html = "<html><head><title>t</title><style>p {}</style></head><body>\
+            <h1>run</h1>verb<br>to move fast<div><p>one</p><p>two</p></div>\
+            <script>alert(1)</script><pre>  a\n  b</pre></body></html>";
+        assert_eq!(
+            html_to_text(html),
+            "run\nverb\nto move fast\none\ntwo\n  a\n  b\n"
+        );
+    }
+
+    #[test]
+    fn whitespace() {
+        assert_eq!(
+            html_to_text("<p>a  <b>bold</b>\n word</p>\n\n\n<p>b</p>"),
+            "a bold word\nb\n"
+        );
+        assert_eq!(tidy("a  \n\n\n\nb\n\n"), "a\n\nb\n");
+    }
+
+    #[test]
+    fn lists() {
+        let html = "<ol><li>first</li><li>second<ul><li>inner</li></ul></li></ol>";
+        assert_eq!(html_to_text(html), "• first\n• second\n• inner\n");
+        assert_eq!(html_to_ansi(html, 80), "1. first\n2. second\n  • inner\n");
+    }
+
+    #[test]
+    fn nested_styles() {
+        assert_eq!(
+            html_to_ansi("<i>an <b>apple</b></i> <span class='phon'>/æp/</span>", 80),
+            "\x1b[3man\x1b[0m \x1b[3;1mapple\x1b[0m \x1b[36m/æp/\x1b[0m\n"
+        );
+        // plain text has no escape codes
+        assert_eq!(html_to_text("<i>an <b>apple</b></i>"), "an apple\n");
+    }
+
+    #[test]
+    fn wrapping() {
+        assert_eq!(
+            wrap("• one two three four", 10),
+            "• one two\n  three\n  four\n"
+        );
+        assert_eq!(wrap("  12. aaa bbb", 10), "  12. aaa\n      bbb\n");
+    }
+
+    #[test]
+    fn cjk_width() {
+        // two columns a character
+        assert_eq!(wrap("一二三四五六", 8), "一二三四\n五六\n");
+        // a full-width space is two columns of indent
+        assert_eq!(
+            wrap("\u{3000}一二三 四五六", 8),
+            "\u{3000}一二三\n  四五六\n"
+        );
+    }
+}
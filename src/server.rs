@@ -0,0 +1,326 @@
+//! Long-running lookup server with a JSON/HTML api.
+//!
+//! `GET /lookup?word=awesome&dict=<id>` returns the results as json,
+//! add `&format=html` for the same page as the cli.
+//! `GET /entry/<id>?word=awesome` is the result of one dictionary, the css, js and images
+//! it references are read from the dictionary on request under `/res/<id>/<resource path>`.
+use crate::headword_index::Mask;
+use crate::library::{load_library, Database, Library};
+use crate::result_page;
+use crate::suggest::{suggest, suggestion_page, LIMIT};
+use crate::utils::percent_encode;
+use anyhow::Result;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use axum_extra::TypedHeader;
+use headers::{HeaderMapExt, IfModifiedSince, LastModified};
+use log::*;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tower_http::trace::TraceLayer;
+
+/// resources rarely change, let the browser keep them for a day
+const CACHE_CONTROL: &str = "public, max-age=86400";
+
+struct AppState {
+    library: Library,
+}
+
+#[derive(Debug, Deserialize)]
+struct LookupParams {
+    word: String,
+    /// only look up in the dictionary of this id
+    dict: Option<String>,
+    /// `json` (default) or `html`
+    format: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompleteParams {
+    word: String,
+    dict: Option<String>,
+    /// `prefix` (default), `substring` or `fuzzy`
+    mode: Option<String>,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EntryParams {
+    word: String,
+}
+
+#[derive(Debug, Serialize)]
+struct LookupResult {
+    word: String,
+    results: Vec<Entry>,
+    /// closest headwords when nothing is found
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    suggestions: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Entry {
+    id: String,
+    dictionary: String,
+    path: PathBuf,
+    /// the result page, resources included
+    url: String,
+    html: String,
+}
+
+#[derive(Debug, Serialize)]
+struct DictInfo {
+    id: String,
+    name: String,
+    path: PathBuf,
+}
+
+impl AppState {
+    fn find(&self, id: &str) -> Option<&Database> {
+        self.library.databases.iter().find(|db| db.id == id)
+    }
+
+    fn lookup(&self, word: &str, dict: Option<&str>) -> Vec<Entry> {
+        self.library
+            .databases
+            .par_iter()
+            .filter(|db| dict.is_none_or(|id| id == db.id))
+            .filter_map(|db| {
+                let dict = db.dict.lock().unwrap();
+                let html = dict.definition(word).ok()?;
+                Some(Entry {
+                    id: db.id.clone(),
+                    dictionary: dict.name().to_owned(),
+                    path: dict.path().to_path_buf(),
+                    url: format!("/entry/{}?word={}", db.id, percent_encode(word)),
+                    html,
+                })
+            })
+            .collect()
+    }
+
+    fn suggestions(&self, word: &str, dict: Option<&str>) -> Vec<String> {
+        let candidates: Vec<String> = self
+            .library
+            .databases
+            .par_iter()
+            .filter(|db| dict.is_none_or(|id| id == db.id))
+            .flat_map_iter(|db| db.suggestions(&self.library.index, word))
+            .collect();
+        suggest(word, candidates, LIMIT)
+    }
+
+    /// the mask of `dict` in the headword index, `None` if there's no such dictionary
+    fn mask(&self, dict: Option<&str>) -> Option<Mask> {
+        match dict {
+            Some(id) => self.find(id).map(|db| db.mask),
+            None => Some(Mask::All),
+        }
+    }
+}
+
+pub async fn serve(addr: &str) -> Result<()> {
+    let app_state = Arc::new(AppState {
+        library: load_library()?,
+    });
+
+    let app = Router::new()
+        .route("/lookup", get(lookup))
+        .route("/complete", get(complete))
+        .route("/dicts", get(dicts))
+        .route("/entry/:id", get(entry))
+        .route("/res/:id/*path", get(resource))
+        .with_state(app_state)
+        .layer(TraceLayer::new_for_http());
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    println!("open http://{addr}/lookup?word=awesome&format=html");
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn lookup(
+    State(app_state): State<Arc<AppState>>,
+    Query(params): Query<LookupParams>,
+) -> Response {
+    let word = params.word.clone();
+    let dict = params.dict.clone();
+    let state = app_state.clone();
+    let found = tokio::task::spawn_blocking(move || {
+        let entries = state.lookup(&word, dict.as_deref());
+        let suggestions = if entries.is_empty() {
+            state.suggestions(&word, dict.as_deref())
+        } else {
+            Vec::new()
+        };
+        (entries, suggestions)
+    })
+    .await;
+    let (entries, suggestions) = match found {
+        Ok(found) => found,
+        Err(e) => {
+            error!("{e}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+        }
+    };
+    let status = if entries.is_empty() {
+        StatusCode::NOT_FOUND
+    } else {
+        StatusCode::OK
+    };
+
+    match params.format.as_deref() {
+        Some("html") if entries.is_empty() => {
+            let page = suggestion_page(&params.word, &suggestions, |s| {
+                let mut href = format!("/lookup?word={}&format=html", percent_encode(s));
+                if let Some(dict) = &params.dict {
+                    href.push_str(&format!("&dict={}", percent_encode(dict)));
+                }
+                href
+            });
+            (status, Html(page)).into_response()
+        }
+        Some("html") => {
+            let open = entries
+                .iter()
+                .position(|e| {
+                    app_state
+                        .find(&e.id)
+                        .is_some_and(|db| db.dict.lock().unwrap().open())
+                })
+                .unwrap_or(0);
+            let buttons: Vec<_> = entries.into_iter().map(|e| (e.url, e.dictionary)).collect();
+            (status, Html(result_page(&buttons, open, None))).into_response()
+        }
+        _ => (
+            status,
+            Json(LookupResult {
+                word: params.word,
+                results: entries,
+                suggestions,
+            }),
+        )
+            .into_response(),
+    }
+}
+
+/// headwords for autocompletion
+async fn complete(
+    State(app_state): State<Arc<AppState>>,
+    Query(params): Query<CompleteParams>,
+) -> Response {
+    let Some(mask) = app_state.mask(params.dict.as_deref()) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let index = &app_state.library.index;
+    let limit = params.limit.unwrap_or(20);
+    let words = match params.mode.as_deref() {
+        None | Some("prefix") => index.prefix(&params.word, mask, limit),
+        Some("substring") => index.substring(&params.word, mask, limit),
+        Some("fuzzy") => match index.fuzzy(&params.word, 1, mask, limit) {
+            Ok(words) => words,
+            Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+        },
+        Some(mode) => {
+            return (StatusCode::BAD_REQUEST, format!("unknown mode {mode}")).into_response()
+        }
+    };
+    Json(words).into_response()
+}
+
+/// relative links of the entry resolve to `/res/<id>/`, apart from the other routes
+fn with_base(html: &str, id: &str) -> String {
+    let base = format!(r#"<base href="/res/{}/">"#, percent_encode(id));
+    match html.to_ascii_lowercase().find("<head>") {
+        Some(i) => format!("{}{base}{}", &html[..i + 6], &html[i + 6..]),
+        None => format!("{base}{html}"),
+    }
+}
+
+async fn entry(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<EntryParams>,
+) -> Response {
+    let html = tokio::task::spawn_blocking(move || {
+        let db = app_state.find(&id)?;
+        let html = db.dict.lock().unwrap().definition(&params.word).ok()?;
+        Some(with_base(&html, &id))
+    })
+    .await;
+    match html {
+        Ok(Some(html)) => Html(html).into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn resource(
+    State(app_state): State<Arc<AppState>>,
+    Path((id, path)): Path<(String, String)>,
+    if_modified_since: Option<TypedHeader<IfModifiedSince>>,
+) -> Response {
+    let Some(db) = app_state.find(&id) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    // the dictionary file changes when its resources do
+    let modified = fs::metadata(db.dict.lock().unwrap().path()).and_then(|m| m.modified());
+    if let (Ok(modified), Some(TypedHeader(since))) = (&modified, if_modified_since) {
+        if !since.is_modified(*modified) {
+            return StatusCode::NOT_MODIFIED.into_response();
+        }
+    }
+
+    let mime = mime_guess::from_path(&path).first_or_octet_stream();
+    let data = tokio::task::spawn_blocking(move || {
+        let db = app_state.find(&id).unwrap();
+        db.dict.lock().unwrap().resource(&path)
+    })
+    .await;
+    match data {
+        Ok(Ok(data)) => {
+            let mut response = (
+                [
+                    (header::CONTENT_TYPE, mime.to_string()),
+                    (header::CACHE_CONTROL, CACHE_CONTROL.to_owned()),
+                ],
+                data,
+            )
+                .into_response();
+            if let Ok(modified) = modified {
+                response
+                    .headers_mut()
+                    .typed_insert(LastModified::from(modified));
+            }
+            response
+        }
+        Ok(Err(e)) => {
+            debug!("{e}");
+            StatusCode::NOT_FOUND.into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn dicts(State(app_state): State<Arc<AppState>>) -> Json<Vec<DictInfo>> {
+    Json(
+        app_state
+            .library
+            .databases
+            .iter()
+            .map(|db| {
+                let dict = db.dict.lock().unwrap();
+                DictInfo {
+                    id: db.id.clone(),
+                    name: dict.name().to_owned(),
+                    path: dict.path().to_path_buf(),
+                }
+            })
+            .collect(),
+    )
+}
@@ -1,23 +1,36 @@
 //! copy and modified from <https://github.com/vaaandark/dioxionary/blob/master/src/stardict.rs>
 //! Look up words form the offline stardicts.
+//!
+//! The idx and the dict are memory mapped and read in place, a dict.dz is inflated
+//! chunk by chunk when it's a dictzip.
+use crate::cache;
+use crate::normalize::normalize;
 use crate::utils::create_sub_dir;
 use crate::utils::groom_name;
+use crate::utils::safe_relative_path;
 use crate::T;
 use anyhow::{anyhow, Context, Result};
-use eio::FromBytes;
 use flate2::read::GzDecoder;
-use std::cell::OnceCell;
-use std::error::Error;
-use std::fmt::{Debug, Display};
-use std::fs::{self, read, File};
+use flate2::{Decompress, FlushDecompress};
+use fst::Map;
+use log::*;
+use memmap2::Mmap;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt::Display;
+use std::fs::{self, File};
 use std::io::{prelude::*, BufReader};
 use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
 
-/// The stardict to be looked up.
+/// The stardict to be looked up, the ifo, idx and dict are opened on first use.
 pub struct StarDict {
-    ifo: Ifo,
-    idx: Idx,
-    dict: Dict,
+    name: String,
+    dict_type: DictType,
+    idx: OnceLock<Result<Idx>>,
+    dict: OnceLock<Result<Dict>>,
+    /// normalized word -> index in idx, opened on the first miss
+    normalized: OnceLock<Option<Map<Mmap>>>,
 }
 
 /// A word entry of the stardict.
@@ -33,45 +46,100 @@ pub struct EntryWrapper<'b> {
 
 impl std::fmt::Display for EntryWrapper<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "{} {}", self.entry.word, self.dict_name)
+        write!(f, "{} {}", self.entry.word, self.dict_name)
     }
 }
 
 impl StarDict {
-    pub fn dz(dz_path: &Path) -> Result<StarDict> {
-        let ifo = Ifo::new(dz_path.with_extension("ifo"))?;
-        let idx = Idx::new(dz_path.with_extension("idx"), ifo.version)?;
-        let dict = Dict::new(DictType::Dz(dz_path.to_path_buf()));
+    /// `name` is the bookname of the ifo, see `bookname`
+    pub fn dz(dz_path: &Path, name: String) -> Result<StarDict> {
+        Self::new(DictType::Dz(dz_path.to_path_buf()), name)
+    }
 
-        Ok(StarDict { ifo, idx, dict })
+    pub fn dict(dict_path: &Path, name: String) -> Result<StarDict> {
+        Self::new(DictType::Dict(dict_path.to_path_buf()), name)
     }
 
-    pub fn dict(dict_path: &Path) -> Result<StarDict> {
-        let ifo = Ifo::new(dict_path.with_extension("ifo"))?;
-        let idx = Idx::new(dict_path.with_extension("idx"), ifo.version)?;
-        let dict = Dict::new(DictType::Dict(dict_path.to_path_buf()));
+    fn new(dict_type: DictType, name: String) -> Result<StarDict> {
+        let idx_path = sibling(dict_type.path(), "idx");
+        if !idx_path.is_file() {
+            return Err(anyhow!("no idx file {:?}", idx_path));
+        }
+        Ok(StarDict {
+            name,
+            dict_type,
+            idx: OnceLock::new(),
+            dict: OnceLock::new(),
+            normalized: OnceLock::new(),
+        })
+    }
 
-        Ok(StarDict { ifo, idx, dict })
+    /// the bookname in the ifo next to the dict at `path`
+    pub fn bookname(path: &Path) -> Result<String> {
+        Ok(Ifo::new(sibling(path, "ifo"))?.bookname)
     }
-}
 
-impl StarDict {
-    fn exact_lookup(&self, word: &str) -> Option<&str> {
-        let word = word.to_lowercase();
-        if let Ok(pos) = self
-            .idx
-            .items
-            .binary_search_by(|probe| probe.0.to_lowercase().cmp(&word))
+    fn idx(&self) -> Result<&Idx> {
+        self.idx
+            .get_or_init(|| {
+                let path = self.dict_type.path();
+                let ifo = Ifo::new(sibling(path, "ifo"))?;
+                Idx::new(&sibling(path, "idx"), &ifo)
+            })
+            .as_ref()
+            .map_err(|e| anyhow!("{e:?}"))
+    }
+
+    fn body(&self) -> Result<&Dict> {
+        self.dict
+            .get_or_init(|| Dict::new(&self.dict_type))
+            .as_ref()
+            .map_err(|e| anyhow!("{e:?}"))
+    }
+
+    fn exact_lookup(&self, word: &str) -> Option<String> {
+        let idx = match self.idx() {
+            Ok(idx) => idx,
+            Err(e) => {
+                error!("{e}");
+                return None;
+            }
+        };
+        let i = match idx.find(word) {
+            Some(i) => i,
+            None => self.normalized_lookup(idx, word)?,
+        };
+        let item = idx.nth(i)?;
+        match self
+            .body()
+            .and_then(|dict| dict.get(item.offset, item.size))
         {
-            let (_word, offset, size) = &self.idx.items[pos];
-            self.dict.get(*offset, *size).ok()
-        } else {
-            None
+            Ok(entry) => Some(String::from_utf8_lossy(&entry).into_owned()),
+            Err(e) => {
+                error!("{}: {e}", self.name);
+                None
+            }
         }
     }
 
+    fn normalized_lookup(&self, idx: &Idx, word: &str) -> Option<usize> {
+        let normalized = self.normalized.get_or_init(|| {
+            let path = sibling(self.dict_type.path(), "idx");
+            cache::normalized(&path, || {
+                Ok(idx
+                    .items()
+                    .enumerate()
+                    .map(|(i, item)| (item.word(), i as u64))
+                    .collect())
+            })
+            .inspect_err(|e| error!("{e:?}"))
+            .ok()
+        });
+        Some(normalized.as_ref()?.get(normalize(word))? as usize)
+    }
+
     fn dict_name(&self) -> &str {
-        &self.ifo.bookname
+        &self.name
     }
 }
 
@@ -212,30 +280,21 @@ impl Display for Ifo {
     }
 }
 
+/// the file with `extension` next to the dict at `path`, foo.ifo for foo.dict.dz
+pub fn sibling(path: &Path, extension: &str) -> PathBuf {
+    let mut path = path.to_path_buf();
+    if path.extension().is_some_and(|e| e == "dz") {
+        path.set_extension("");
+    }
+    path.with_extension(extension)
+}
+
 enum DictType {
     Dz(PathBuf),
     Dict(PathBuf),
 }
 
 impl DictType {
-    fn load_dz(path: &Path) -> Result<String> {
-        let s =
-            read(path).with_context(|| format!("Failed to open stardict directory {:?}", path))?;
-        let mut d = GzDecoder::new(s.as_slice());
-        let mut contents = String::new();
-        d.read_to_string(&mut contents).with_context(|| {
-            format!("Failed to open stardict directory {:?} as dz format", path)
-        })?;
-        Ok(contents)
-    }
-
-    fn load(&self) -> Result<String> {
-        match self {
-            DictType::Dz(path) => Self::load_dz(path),
-            DictType::Dict(path) => Ok(fs::read_to_string(path)?),
-        }
-    }
-
     fn path(&self) -> &Path {
         match self {
             DictType::Dz(path_buf) => path_buf,
@@ -244,93 +303,319 @@ impl DictType {
     }
 }
 
-pub struct Dict {
-    contents: OnceCell<Result<String>>,
-    dict_type: DictType,
+/// open `path` memory mapped
+fn map(path: &Path) -> Result<Mmap> {
+    let file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    // dictionaries are only read, a file changed while it's mapped is a broken dictionary
+    unsafe { Mmap::map(&file) }.with_context(|| format!("Failed to map {:?}", path))
+}
+
+/// The definitions.
+enum Dict {
+    Plain(Mmap),
+    DictZip(DictZip),
+    /// gzip without the chunk table, inflated whole
+    Gzip(Vec<u8>),
+}
+
+/// A dict.dz: gzip made of chunks which can be inflated alone,
+/// <https://manpages.debian.org/dictzip>
+struct DictZip {
+    data: Mmap,
+    /// the length of a chunk inflated
+    chunk_len: usize,
+    /// where the chunks start in `data`, and where the last one ends
+    chunks: Vec<usize>,
+    /// the last chunk inflated, definitions are often read in order
+    last: Mutex<Option<(usize, Vec<u8>)>>,
 }
 
 impl Dict {
-    fn new(dict_type: DictType) -> Self {
-        Self {
-            dict_type,
-            contents: OnceCell::new(),
+    fn new(dict_type: &DictType) -> Result<Dict> {
+        match dict_type {
+            DictType::Dict(path) => Ok(Dict::Plain(map(path)?)),
+            DictType::Dz(path) => {
+                let data = map(path)?;
+                let chunks = DictZip::chunks(&data)
+                    .with_context(|| format!("Failed to open {:?} as dictzip", path))?;
+                match chunks {
+                    Some((chunk_len, chunks)) => Ok(Dict::DictZip(DictZip {
+                        data,
+                        chunk_len,
+                        chunks,
+                        last: Mutex::new(None),
+                    })),
+                    None => {
+                        let mut contents = Vec::new();
+                        GzDecoder::new(&data[..])
+                            .read_to_end(&mut contents)
+                            .with_context(|| {
+                                format!("Failed to open stardict directory {:?} as dz format", path)
+                            })?;
+                        Ok(Dict::Gzip(contents))
+                    }
+                }
+            }
         }
     }
 
-    fn get(&self, offset: usize, size: usize) -> Result<&str> {
-        let res = self
-            .contents
-            .get_or_init(|| self.dict_type.load())
-            .as_ref()
-            .map(|x| x.get(offset..offset + size));
+    fn get(&self, offset: usize, size: usize) -> Result<Cow<'_, [u8]>> {
+        let range = offset..offset + size;
+        match self {
+            Dict::Plain(data) => data.get(range).map(Cow::Borrowed),
+            Dict::Gzip(data) => data.get(range).map(Cow::Borrowed),
+            Dict::DictZip(dz) => dz.get(offset, size)?.map(Cow::Owned),
+        }
+        .with_context(|| format!("{size} bytes at {offset} out of the dict"))
+    }
+}
 
-        match res {
-            Ok(Some(res)) => Ok(res),
-            Ok(None) => Err(anyhow!("not found")),
-            Err(_error) => Err(anyhow!("not found")), // TODO: lost _error here
+impl DictZip {
+    /// the chunk length and where the chunks start, `None` if it's not a dictzip,
+    /// an error if its RA header can't be read from
+    fn chunks(data: &[u8]) -> Result<Option<(usize, Vec<usize>)>> {
+        let Some((chunk_len, chunks)) = DictZip::header(data) else {
+            return Ok(None);
+        };
+        if chunk_len == 0 {
+            return Err(anyhow!("chunk length of 0"));
         }
+        // the gzip trailer ends with the inflated length
+        let inflated = data
+            .len()
+            .checked_sub(4)
+            .filter(|&pos| pos >= chunks[0] + 4)
+            .map_or(0, |pos| u32::from_le_bytes(data[pos..].try_into().unwrap()));
+        if chunks.len() == 1 && inflated != 0 {
+            return Err(anyhow!("no chunks for {inflated} bytes"));
+        }
+        Ok(Some((chunk_len, chunks)))
+    }
+
+    /// the chunk length and where the chunks start as the RA header says
+    fn header(data: &[u8]) -> Option<(usize, Vec<usize>)> {
+        if data.get(..3)? != [0x1f, 0x8b, 8] {
+            return None;
+        }
+        let flags = data[3];
+        let u16_at = |pos: usize| -> Option<usize> {
+            Some(u16::from_le_bytes(data.get(pos..pos + 2)?.try_into().unwrap()) as usize)
+        };
+        // FEXTRA
+        if flags & 4 == 0 {
+            return None;
+        }
+        let extra_len = u16_at(10)?;
+        let mut pos = 12;
+        let end = pos + extra_len;
+        let mut ra = None;
+        while pos + 4 <= end {
+            let len = u16_at(pos + 2)?;
+            if data.get(pos..pos + 2)? == b"RA" {
+                ra = Some(pos + 4);
+            }
+            pos += 4 + len;
+        }
+        // version, chunk length, chunk count then the compressed length of each chunk
+        let ra = ra?;
+        let chunk_len = u16_at(ra + 2)?;
+        let count = u16_at(ra + 4)?;
+
+        let mut pos = end;
+        // FNAME and FCOMMENT end with a nul
+        for flag in [8, 16] {
+            if flags & flag != 0 {
+                pos += data.get(pos..)?.iter().position(|&b| b == 0)? + 1;
+            }
+        }
+        // FHCRC
+        if flags & 2 != 0 {
+            pos += 2;
+        }
+        let mut chunks = vec![pos];
+        for i in 0..count {
+            pos += u16_at(ra + 6 + 2 * i)?;
+            chunks.push(pos);
+        }
+        Some((chunk_len, chunks))
+    }
+
+    fn get(&self, offset: usize, size: usize) -> Result<Option<Vec<u8>>> {
+        let first = offset / self.chunk_len;
+        let last = (offset + size).div_ceil(self.chunk_len);
+        if last >= self.chunks.len() {
+            return Ok(None);
+        }
+        let mut data = Vec::new();
+        for i in first..last {
+            data.extend_from_slice(&self.chunk(i)?);
+        }
+        let start = offset - first * self.chunk_len;
+        Ok(data.get(start..start + size).map(<[u8]>::to_vec))
+    }
+
+    fn chunk(&self, i: usize) -> Result<Vec<u8>> {
+        let mut last = self.last.lock().unwrap();
+        if let Some((n, chunk)) = last.as_ref() {
+            if *n == i {
+                return Ok(chunk.clone());
+            }
+        }
+        let raw = self
+            .data
+            .get(self.chunks[i]..self.chunks[i + 1])
+            .with_context(|| format!("chunk {i} out of the dict.dz"))?;
+        // each chunk ends with a full flush, it's raw deflate without the end of the stream
+        let mut inflate = Decompress::new(false);
+        let mut chunk = Vec::with_capacity(self.chunk_len);
+        while (inflate.total_in() as usize) < raw.len() {
+            let before = (inflate.total_in(), inflate.total_out());
+            chunk.reserve(self.chunk_len);
+            inflate
+                .decompress_vec(
+                    &raw[inflate.total_in() as usize..],
+                    &mut chunk,
+                    FlushDecompress::Sync,
+                )
+                .with_context(|| format!("Failed to inflate chunk {i}"))?;
+            if (inflate.total_in(), inflate.total_out()) == before {
+                break;
+            }
+        }
+        *last = Some((i, chunk.clone()));
+        Ok(chunk)
     }
 }
 
-#[derive(Debug)]
+/// entries between two positions kept by `Idx`
+const PAGE: usize = 32;
+
+/// The index, memory mapped and searched in place. The words are sorted ascii case
+/// insensitively, as StarDict compares them.
 pub struct Idx {
-    items: Vec<(String, usize, usize)>,
+    data: Mmap,
+    /// bytes of the offset of a definition, 8 with `idxoffsetbits=64`
+    offset_len: usize,
+    /// the position of every `PAGE`th entry
+    pages: Vec<usize>,
+}
+
+/// An entry of the index.
+struct Item<'a> {
+    word: &'a [u8],
+    offset: usize,
+    size: usize,
+}
+
+impl Item<'_> {
+    fn word(&self) -> String {
+        String::from_utf8_lossy(self.word)
+            .chars()
+            .filter(|&c| c != '\u{fffd}')
+            .collect()
+    }
+}
+
+fn ascii_casecmp(a: &[u8], b: &[u8]) -> Ordering {
+    a.iter()
+        .map(u8::to_ascii_lowercase)
+        .cmp(b.iter().map(u8::to_ascii_lowercase))
 }
 
 impl Idx {
-    fn read_bytes<const N: usize, T>(path: PathBuf) -> Result<Self>
-    where
-        T: FromBytes<N> + TryInto<usize>,
-        <T as TryInto<usize>>::Error: Debug + Error + Send + Sync + 'static,
-    {
-        let f = File::open(&path).with_context(|| format!("Failed to open idx file {:?}", path))?;
-        let mut f = BufReader::new(f);
-
-        let mut items: Vec<_> = Vec::new();
-
-        let mut buf: Vec<u8> = Vec::new();
-        let mut b = [0; N];
-        loop {
-            buf.clear();
-
-            let read_bytes = f
-                .read_until(0, &mut buf)
-                .with_context(|| format!("Failed to parse idx file {:?}", path))?;
-
-            if read_bytes == 0 {
-                break;
+    fn new(path: &Path, ifo: &Ifo) -> Result<Idx> {
+        let offset_len = match ifo.version {
+            Version::V300 if ifo.idxoffsetbits == 64 => 8,
+            Version::V242 | Version::V300 => 4,
+            Version::Unknown => {
+                return Err(anyhow!("Wrong stardict version in idx file {:?}", path))
             }
+        };
+        let mut idx = Idx {
+            data: map(path)?,
+            offset_len,
+            pages: Vec::new(),
+        };
+        let pages = cache::pages(path, || idx.scan(path))?;
+        idx.pages = pages;
+        Ok(idx)
+    }
 
-            if buf.last() == Some(&b'\0') {
-                buf.pop();
+    /// where every `PAGE`th entry starts, reading through the idx
+    fn scan(&self, path: &Path) -> Result<Vec<usize>> {
+        let mut pages = Vec::new();
+        let mut pos = 0;
+        for i in 0.. {
+            if pos >= self.data.len() {
+                break;
             }
+            if i % PAGE == 0 {
+                pages.push(pos);
+            }
+            pos = self
+                .item(pos)
+                .with_context(|| format!("Failed to parse idx file {:?}", path))?
+                .1;
+        }
+        Ok(pages)
+    }
 
-            let word: String = String::from_utf8_lossy(&buf)
-                .chars()
-                .filter(|&c| c != '\u{fffd}')
-                .collect();
+    /// the entry at `pos` and the position of the next one
+    fn item(&self, pos: usize) -> Option<(Item<'_>, usize)> {
+        let rest = self.data.get(pos..)?;
+        let len = rest.iter().position(|&b| b == 0)?;
+        let numbers = rest.get(len + 1..len + 1 + self.offset_len + 4)?;
+        let (offset, size) = numbers.split_at(self.offset_len);
+        let item = Item {
+            word: &rest[..len],
+            offset: offset.iter().fold(0, |n, &b| n << 8 | b as usize),
+            size: u32::from_be_bytes(size.try_into().unwrap()) as usize,
+        };
+        Some((item, pos + len + 1 + numbers.len()))
+    }
 
-            f.read(&mut b)
-                .with_context(|| format!("Failed to parse idx file {:?}", path))?;
-            let offset = T::from_be_bytes(b).try_into()?;
+    /// the entries from the one at `pos`
+    fn items_from(&self, mut pos: usize) -> impl Iterator<Item = Item<'_>> {
+        std::iter::from_fn(move || {
+            let (item, next) = self.item(pos)?;
+            pos = next;
+            Some(item)
+        })
+    }
 
-            f.read(&mut b)
-                .with_context(|| format!("Failed to parse idx file {:?}", path))?;
-            let size = T::from_be_bytes(b).try_into()?;
+    fn items(&self) -> impl Iterator<Item = Item<'_>> {
+        self.items_from(0)
+    }
 
-            if !word.is_empty() {
-                items.push((word, offset, size))
-            }
-        }
-        Ok(Self { items })
+    fn nth(&self, i: usize) -> Option<Item<'_>> {
+        let pos = *self.pages.get(i / PAGE)?;
+        self.items_from(pos).nth(i % PAGE)
     }
 
-    fn new(path: PathBuf, version: Version) -> Result<Idx> {
-        match version {
-            Version::V242 => Ok(Idx::read_bytes::<4, u32>(path)?),
-            Version::V300 => Ok(Idx::read_bytes::<8, u64>(path)?),
-            Version::Unknown => Err(anyhow!("Wrong stardict version in idx file {:?}", path)),
+    /// the index of the entry which is `word`, the first one which is `word` ignoring
+    /// ascii case if none is
+    fn find(&self, word: &str) -> Option<usize> {
+        let word = word.as_bytes();
+        // it's in the last page starting before `word`, or at the start of the next one
+        let page = self
+            .pages
+            .partition_point(|&pos| {
+                self.item(pos)
+                    .is_some_and(|(item, _)| ascii_casecmp(item.word, word).is_lt())
+            })
+            .saturating_sub(1);
+        let mut found = None;
+        for (i, item) in self.items_from(*self.pages.get(page)?).enumerate() {
+            match ascii_casecmp(item.word, word) {
+                Ordering::Less => {}
+                Ordering::Equal if item.word == word => return Some(page * PAGE + i),
+                Ordering::Equal => {
+                    found.get_or_insert(page * PAGE + i);
+                }
+                Ordering::Greater => break,
+            }
         }
+        found
     }
 }
 
@@ -340,7 +625,44 @@ impl T for StarDict {
     }
 
     fn path(&self) -> &Path {
-        self.dict.dict_type.path()
+        self.dict_type.path()
+    }
+
+    fn files(&self) -> Vec<PathBuf> {
+        let path = self.dict_type.path();
+        vec![
+            sibling(path, "ifo"),
+            sibling(path, "idx"),
+            path.to_path_buf(),
+        ]
+    }
+
+    fn definition(&self, word: &str) -> Result<String> {
+        match self.exact_lookup(word) {
+            Some(entry) => Ok(entry),
+            None => Result::Err(anyhow!("not found")),
+        }
+    }
+
+    /// files under `res/` next to the dict
+    fn resource(&self, path: &str) -> Result<Vec<u8>> {
+        let rel = safe_relative_path(path.trim_start_matches('/'))
+            .with_context(|| format!("bad path {path}"))?;
+        let p = T::path(self).parent().unwrap().join("res").join(rel);
+        fs::read(&p).with_context(|| format!("Failed to read {:?}", p))
+    }
+
+    fn headwords(&self) -> Result<Vec<String>> {
+        Ok(self
+            .idx()?
+            .items()
+            .map(|item| item.word())
+            .filter(|word| !word.is_empty())
+            .collect())
+    }
+
+    fn full_text(&self) -> bool {
+        true
     }
 
     fn lookup(&self, word: &str, base_dir: &std::path::Path) -> Result<PathBuf> {
@@ -356,3 +678,136 @@ impl T for StarDict {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::{Compress, Compression, FlushCompress};
+
+    const TEXT: &[u8] = b"apple: a red fruit\nbanana: a long fruit\ncherry: small";
+
+    /// `TEXT` as a dictzip of `chunk_len` chunks, with a file name
+    fn dictzip(chunk_len: usize) -> Vec<u8> {
+        let mut sizes = Vec::new();
+        let mut body = Vec::new();
+        let mut deflate = Compress::new(Compression::default(), false);
+        for chunk in TEXT.chunks(chunk_len) {
+            let mut out = Vec::with_capacity(chunk.len() + 64);
+            let before = deflate.total_out();
+            deflate
+                .compress_vec(chunk, &mut out, FlushCompress::Full)
+                .unwrap();
+            sizes.push((deflate.total_out() - before) as u16);
+            body.extend(out);
+        }
+
+        let mut ra = Vec::new();
+        for n in [1, chunk_len as u16, sizes.len() as u16]
+            .iter()
+            .chain(&sizes)
+        {
+            ra.extend(n.to_le_bytes());
+        }
+        let mut dz = vec![0x1f, 0x8b, 8, 4 | 8, 0, 0, 0, 0, 2, 3];
+        dz.extend(((4 + ra.len()) as u16).to_le_bytes());
+        dz.extend(b"RA");
+        dz.extend((ra.len() as u16).to_le_bytes());
+        dz.extend(ra);
+        dz.extend(b"t.dict\0");
+        dz.extend(body);
+        dz
+    }
+
+    fn open(data: &[u8]) -> (tempfile::TempDir, Dict) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("t.dict.dz");
+        fs::write(&path, data).unwrap();
+        let dict = Dict::new(&DictType::Dz(path)).unwrap();
+        (dir, dict)
+    }
+
+    #[test]
+    fn chunk_table() {
+        let dz = dictzip(16);
+        let (chunk_len, chunks) = DictZip::chunks(&dz).unwrap().unwrap();
+        assert_eq!(chunk_len, 16);
+        assert_eq!(chunks.len(), TEXT.len().div_ceil(16) + 1);
+        assert_eq!(chunks[chunks.len() - 1], dz.len());
+        assert_eq!(&dz[chunks[0] - 7..chunks[0]], b"t.dict\0");
+        assert!(chunks.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn not_a_dictzip() {
+        assert!(DictZip::chunks(b"plain text").unwrap().is_none());
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(TEXT).unwrap();
+        let gz = gz.finish().unwrap();
+        assert!(DictZip::chunks(&gz).unwrap().is_none());
+
+        // inflated whole instead
+        let (_dir, dict) = open(&gz);
+        assert!(matches!(dict, Dict::Gzip(_)));
+        assert_eq!(&dict.get(7, 11).unwrap()[..], b"a red fruit");
+    }
+
+    #[test]
+    fn read_across_chunks() {
+        let (_dir, dict) = open(&dictzip(16));
+        assert!(matches!(dict, Dict::DictZip(_)));
+        for offset in 0..TEXT.len() {
+            for size in 0..=TEXT.len() - offset {
+                assert_eq!(
+                    &dict.get(offset, size).unwrap()[..],
+                    &TEXT[offset..offset + size],
+                    "{offset} {size}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn read_out_of_the_dict() {
+        let (_dir, dict) = open(&dictzip(16));
+        assert!(dict.get(TEXT.len() - 2, 3).is_err());
+        assert!(dict.get(TEXT.len() + 20, 1).is_err());
+        assert!(dict.get(0, TEXT.len()).is_ok());
+    }
+
+    #[test]
+    fn bad_ra_header() {
+        let dz = dictzip(16);
+        // version, chunk length then count
+        let ra = 12 + 4 + 2;
+
+        let mut zero_len = dz.clone();
+        zero_len[ra..ra + 2].copy_from_slice(&0u16.to_le_bytes());
+        assert_eq!(
+            DictZip::chunks(&zero_len).unwrap_err().to_string(),
+            "chunk length of 0"
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("t.dict.dz");
+        fs::write(&path, &zero_len).unwrap();
+        assert!(Dict::new(&DictType::Dz(path)).is_err());
+
+        // the trailer: crc then the inflated length
+        let mut no_chunks = dz.clone();
+        no_chunks[ra + 2..ra + 4].copy_from_slice(&0u16.to_le_bytes());
+        no_chunks.extend(0u32.to_le_bytes());
+        no_chunks.extend((TEXT.len() as u32).to_le_bytes());
+        assert_eq!(
+            DictZip::chunks(&no_chunks).unwrap_err().to_string(),
+            format!("no chunks for {} bytes", TEXT.len())
+        );
+
+        // an empty dictzip is fine
+        let start = DictZip::header(&dz).unwrap().1[0];
+        let mut empty = dz[..start].to_vec();
+        empty[ra + 2..ra + 4].copy_from_slice(&0u16.to_le_bytes());
+        empty.extend([3, 0]);
+        empty.extend([0; 8]);
+        assert_eq!(DictZip::chunks(&empty).unwrap().unwrap().1.len(), 1);
+    }
+}
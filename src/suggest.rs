@@ -0,0 +1,103 @@
+//! "Did you mean" suggestions for words not found.
+use crate::headword_index::{HeadwordIndex, Mask};
+use crate::utils::escape_html;
+use log::*;
+use std::io::{self, prelude::*};
+
+/// how many suggestions to offer
+pub const LIMIT: usize = 10;
+
+/// typos allowed, longer words get more
+fn max_distance(word: &str) -> usize {
+    match word.chars().count() {
+        0..=4 => 1,
+        5..=8 => 2,
+        _ => 3,
+    }
+}
+
+/// headwords of the dictionaries in `mask` worth ranking by `suggest`
+pub fn candidates(index: &HeadwordIndex, word: &str, mask: Mask) -> Vec<String> {
+    let mut v = index
+        .fuzzy(word, max_distance(word) as u32, mask, 1000)
+        .unwrap_or_else(|e| {
+            debug!("{e}");
+            Vec::new()
+        });
+    // the word may be cut short
+    v.extend(index.prefix(word, mask, LIMIT));
+    v
+}
+
+/// the headwords closest to `word`, best first
+pub fn suggest<I>(word: &str, headwords: I, limit: usize) -> Vec<String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let word = word.to_lowercase();
+    let max = max_distance(&word);
+    let mut scored: Vec<(usize, f64, String)> = headwords
+        .into_iter()
+        .filter_map(|w| {
+            let lower = w.to_lowercase();
+            if lower == word {
+                return None;
+            }
+            let distance = strsim::damerau_levenshtein(&word, &lower);
+            let similarity = strsim::jaro_winkler(&word, &lower);
+            (distance <= max || similarity >= 0.9).then_some((distance, similarity, w))
+        })
+        .collect();
+    scored.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.total_cmp(&a.1)).then(a.2.cmp(&b.2)));
+
+    let mut v: Vec<String> = Vec::new();
+    for (_, _, w) in scored {
+        if v.len() == limit {
+            break;
+        }
+        if !v.iter().any(|x| x.eq_ignore_ascii_case(&w)) {
+            v.push(w);
+        }
+    }
+    v
+}
+
+/// print a numbered list and read the choice, `None` if nothing is chosen
+pub fn select<D: std::fmt::Display>(items: &[D]) -> Option<usize> {
+    for (i, item) in items.iter().enumerate() {
+        println!("{:>3}. {item}", i + 1);
+    }
+    print!("select a number (enter to quit): ");
+    io::stdout().flush().ok()?;
+    let mut line = String::new();
+    io::stdin().read_line(&mut line).ok()?;
+    let n: usize = line.trim().parse().ok()?;
+    (1..=items.len()).contains(&n).then(|| n - 1)
+}
+
+/// a page linking each suggestion to `href(suggestion)`
+pub fn suggestion_page(
+    word: &str,
+    suggestions: &[String],
+    href: impl Fn(&str) -> String,
+) -> String {
+    let items: String = suggestions
+        .iter()
+        .map(|s| {
+            format!(
+                "<li><a href=\"{}\">{}</a></li>\n",
+                escape_html(&href(s)),
+                escape_html(s)
+            )
+        })
+        .collect();
+    let body = if suggestions.is_empty() {
+        "<p>No suggestions.</p>".to_owned()
+    } else {
+        format!("<p>Did you mean:</p>\n<ul>\n{items}</ul>")
+    };
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"UTF-8\"><title>{word}</title></head><body>\n<h3>{word} not found</h3>\n{body}\n</body></html>\n",
+        word = escape_html(word),
+    )
+}
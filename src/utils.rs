@@ -1,6 +1,7 @@
 use anyhow::Context;
 use anyhow::Result;
 use rs_fsrs::Rating;
+use std::path::Component;
 use std::path::Path;
 use std::path::PathBuf;
 
@@ -19,6 +20,73 @@ pub fn groom_name(folder_name: &str) -> String {
     folder_name.replace(|c| c == '\'', "")
 }
 
+pub fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// `s` inside a single quoted javascript string
+pub fn escape_js(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+/// only allow plain relative paths, which stay inside the dir they are joined to
+pub fn safe_relative_path(p: &str) -> Option<&Path> {
+    let path = Path::new(p);
+    path.components()
+        .all(|c| matches!(c, Component::Normal(_)))
+        .then_some(path)
+}
+
+/// percent-encode everything but unreserved characters, for query strings and paths
+pub fn percent_encode(s: &str) -> String {
+    let mut out = String::new();
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-_.~".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{b:02X}"));
+        }
+    }
+    out
+}
+
+/// translate a shell wildcard, `*`, `?` and `[...]`, into an anchored regex
+pub fn wildcard_to_regex(pattern: &str) -> String {
+    let mut re = String::from("^");
+    let mut in_class = false;
+    for c in pattern.chars() {
+        match c {
+            '[' if !in_class => {
+                in_class = true;
+                re.push('[');
+            }
+            ']' if in_class => {
+                in_class = false;
+                re.push(']');
+            }
+            '!' if in_class && re.ends_with('[') => re.push('^'),
+            _ if in_class => {
+                if c == '\\' {
+                    re.push('\\');
+                }
+                re.push(c);
+            }
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            _ => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    if in_class {
+        re.push(']');
+    }
+    re.push('$');
+    re
+}
+
 pub fn create_sub_dir(base_dir: &Path, prefer_name: &str) -> Result<PathBuf> {
     let p = create_sub_dir_inner(base_dir, prefer_name);
     std::fs::create_dir(&p).context(format!("fail to create_dir {:?}", p))?;
@@ -33,8 +101,75 @@ fn create_sub_dir_inner(base_dir: &Path, prefer_name: &str) -> PathBuf {
     for i in 1.. {
         let p = base_dir.join(format!("{prefer_name}-{i}"));
         if !p.exists() {
-            return base_dir.join(prefer_name);
+            return p;
         }
     }
     unreachable!()
 }
+
+/// short unique names of dictionaries, usable in urls and as DICT database names
+pub fn dict_ids<'a>(paths: impl Iterator<Item = &'a Path>) -> Vec<String> {
+    let mut ids: Vec<String> = Vec::new();
+    for path in paths {
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("dict");
+        let id: String = stem
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let mut unique = id.clone();
+        for i in 1.. {
+            if !ids.contains(&unique) {
+                break;
+            }
+            unique = format!("{id}-{i}");
+        }
+        ids.push(unique);
+    }
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    fn matches(pattern: &str, word: &str) -> bool {
+        Regex::new(&wildcard_to_regex(pattern))
+            .unwrap()
+            .is_match(word)
+    }
+
+    #[test]
+    fn wildcard() {
+        assert_eq!(wildcard_to_regex("colo*r"), "^colo.*r$");
+        assert_eq!(wildcard_to_regex("a?c"), "^a.c$");
+        assert_eq!(wildcard_to_regex("[!ab]x"), "^[^ab]x$");
+        assert!(matches("colo*r", "colour"));
+        assert!(matches("colo*r", "color"));
+        assert!(!matches("colo*r", "colors"));
+        assert!(matches("b?t", "bat"));
+        assert!(!matches("b?t", "boat"));
+        assert!(matches("[bc]at", "cat"));
+        assert!(!matches("[!bc]at", "cat"));
+        assert!(matches("[!bc]at", "hat"));
+    }
+
+    #[test]
+    fn wildcard_escapes() {
+        // regex syntax outside of a class is literal
+        assert!(matches("a.b+", "a.b+"));
+        assert!(!matches("a.b+", "axbb"));
+        assert!(matches("(x)", "(x)"));
+        // `!` is only a negation first in a class
+        assert!(matches("[a!]", "!"));
+        assert!(matches("[\\]", "\\"));
+        // an unclosed class is closed
+        assert!(matches("a[bc", "ab"));
+    }
+}
@@ -0,0 +1,388 @@
+//! Showing what was found: in a viewer, carbonyl by default, as text in a pager,
+//! or as json, markdown or html for scripts.
+use crate::config::config;
+use crate::render::{html_to_ansi, html_to_text};
+use crate::utils::{escape_html, percent_encode};
+use crate::{Found, Page};
+use anyhow::{anyhow, Context, Result};
+use log::*;
+use scraper::{Html, Selector};
+use serde::Serialize;
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+/// overrides `viewer` of the config
+pub const VIEWER_ENV: &str = "MDICT_CLI_VIEWER";
+
+/// viewers which return once they are closed, the result page isn't read after that
+const BLOCKING: [&str; 6] = ["carbonyl", "w3m", "lynx", "links", "elinks", "browsh"];
+
+static VIEW: OnceLock<View> = OnceLock::new();
+
+/// how results are shown, set by `--text` and `--format`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum View {
+    Viewer,
+    Text,
+    Json,
+    Markdown,
+    Html,
+}
+
+impl View {
+    pub fn parse(format: &str) -> Result<View> {
+        match format {
+            "text" => Ok(View::Text),
+            "json" => Ok(View::Json),
+            "markdown" | "md" => Ok(View::Markdown),
+            "html" => Ok(View::Html),
+            _ => Err(anyhow!(
+                "unknown format {format}, expected json, markdown, html or text"
+            )),
+        }
+    }
+
+    /// whether the result files outlive the process: only a viewer of `BLOCKING` is done
+    /// with them when it returns, `xdg-open` or a browser may read them later and
+    /// the `none` viewer only prints their path
+    pub fn keeps_files(self) -> bool {
+        self == View::Viewer && !blocks()
+    }
+
+    /// output for scripts, nothing is asked and no history is kept
+    pub fn is_format(self) -> bool {
+        matches!(self, View::Json | View::Markdown | View::Html)
+    }
+}
+
+/// show the results of this run as `view`, before they're looked up
+pub fn set(view: View) {
+    let _ = VIEW.set(view);
+}
+
+/// whether the results are opened in a viewer, which loads the css, js and images
+/// of the pages, so `T::lookup` copies them next to the pages. A viewer reads the
+/// pages as files, which it can't ask for the resources they use
+pub fn shows_resources() -> bool {
+    VIEW.get().is_none_or(|&view| view == View::Viewer)
+}
+
+pub fn show(found: &Found, view: View) -> Result<()> {
+    match view {
+        View::Viewer => {
+            if open(&found.index_html.to_string_lossy())? {
+                Ok(())
+            } else {
+                info!("{} is not installed, showing text", viewer());
+                text(found)
+            }
+        }
+        View::Text => text(found),
+        View::Json => {
+            println!("{}", serde_json::to_string_pretty(&json(found)?)?);
+            Ok(())
+        }
+        View::Markdown => {
+            print!("{}", markdown(found)?);
+            Ok(())
+        }
+        View::Html => {
+            print!("{}", html(found)?);
+            Ok(())
+        }
+    }
+}
+
+/// the viewer command, carbonyl by default
+pub fn viewer() -> String {
+    env::var(VIEWER_ENV)
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .or_else(|| config().viewer.clone())
+        .unwrap_or_else(|| "carbonyl".to_owned())
+}
+
+/// whether the viewer is one of `BLOCKING`
+fn blocks() -> bool {
+    is_blocking(&viewer(), |name| env::var(name).ok())
+}
+
+/// whether the program of `viewer` is one of `BLOCKING`, `var` looks up a `$VAR`
+fn is_blocking(viewer: &str, var: impl Fn(&str) -> Option<String>) -> bool {
+    let Some(program) = viewer.split_whitespace().next() else {
+        return false;
+    };
+    let program = match program.strip_prefix('$') {
+        Some(name) => var(name).unwrap_or_default(),
+        None => program.to_owned(),
+    };
+    let program = program.split(':').next().unwrap_or_default();
+    let program = program.split_whitespace().next().unwrap_or_default();
+    Path::new(program)
+        .file_name()
+        .is_some_and(|name| BLOCKING.iter().any(|b| name == *b))
+}
+
+/// show `target`, a file or an url, in the viewer, false if it isn't installed.
+///
+/// `{path}` in the viewer command is replaced by `target`, `{url}` and `%s` by its url,
+/// `target` is appended when there is none of them.
+/// A word like `$BROWSER` is replaced by the environment variable, `none` prints `target`.
+pub fn open(target: &str) -> Result<bool> {
+    let viewer = viewer();
+    if viewer.trim() == "none" {
+        println!("{target}");
+        return Ok(true);
+    }
+    let mut args = command(&viewer, target, |name| env::var(name).ok())?.into_iter();
+    let program = args.next().unwrap_or_default();
+    match Command::new(&program).args(args).status() {
+        Ok(_) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e).with_context(|| format!("Failed to run {program}")),
+    }
+}
+
+/// the program and arguments `open` runs, `var` looks up a `$VAR`
+fn command(
+    viewer: &str,
+    target: &str,
+    var: impl Fn(&str) -> Option<String>,
+) -> Result<Vec<String>> {
+    let url = if target.contains("://") {
+        target.to_owned()
+    } else {
+        format!(
+            "file://{}",
+            target
+                .split('/')
+                .map(percent_encode)
+                .collect::<Vec<_>>()
+                .join("/")
+        )
+    };
+
+    let mut words: Vec<String> = Vec::new();
+    for word in viewer.split_whitespace() {
+        match word.strip_prefix('$') {
+            Some(name) => {
+                let value = var(name).with_context(|| format!("{name} is not set"))?;
+                // $BROWSER is a list of commands separated by colons
+                let value = value.split(':').next().unwrap_or_default();
+                words.extend(value.split_whitespace().map(str::to_owned));
+            }
+            None => words.push(word.to_owned()),
+        }
+    }
+    if words.is_empty() {
+        return Err(anyhow!("viewer {viewer:?} has no command"));
+    }
+    let templated = words
+        .iter()
+        .any(|w| ["{path}", "{url}", "%s"].iter().any(|p| w.contains(p)));
+    let mut args: Vec<String> = words
+        .into_iter()
+        .map(|w| {
+            w.replace("{path}", target)
+                .replace("{url}", &url)
+                .replace("%s", &url)
+        })
+        .collect();
+    if !templated {
+        args.push(target.to_owned());
+    }
+    Ok(args)
+}
+
+/// the html `T::lookup` wrote
+fn read(page: &Page) -> Result<String> {
+    let path = page.dir.join("index.html");
+    fs::read_to_string(&path).with_context(|| format!("Failed to open {:?}", path))
+}
+
+/// the dictionary, and the headword when it isn't the word looked up
+fn title(found: &Found, page: &Page) -> String {
+    if page.headword == found.word {
+        page.dictionary.clone()
+    } else {
+        format!("{} · {}", page.headword, page.dictionary)
+    }
+}
+
+fn text(found: &Found) -> Result<()> {
+    let width = textwrap::termwidth();
+    let mut s = String::new();
+    if let Some(note) = &found.note {
+        s.push_str(&format!("{note}\n\n"));
+    }
+    for page in &found.pages {
+        s.push_str(&format!("\x1b[1;7m {} \x1b[0m\n\n", title(found, page)));
+        s.push_str(&html_to_ansi(&read(page)?, width));
+        s.push('\n');
+    }
+    pager(&s)
+}
+
+/// print `s` through `$PAGER`, `less -FR` by default, when stdout is a terminal
+fn pager(s: &str) -> Result<()> {
+    if !io::stdout().is_terminal() {
+        print!("{s}");
+        return Ok(());
+    }
+    let pager = env::var("PAGER").unwrap_or_else(|_| "less -FR".to_owned());
+    let mut pager = pager.split_whitespace();
+    let child = pager.next().map(|program| {
+        Command::new(program)
+            .args(pager)
+            .stdin(Stdio::piped())
+            .spawn()
+    });
+    let Some(Ok(mut child)) = child else {
+        print!("{s}");
+        return Ok(());
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // the pager may quit before reading everything
+        let _ = stdin.write_all(s.as_bytes());
+    }
+    child.wait()?;
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    dictionary: &'a str,
+    /// path to the dictionary
+    path: &'a Path,
+    headword: &'a str,
+    html: String,
+    text: String,
+}
+
+fn json(found: &Found) -> Result<Vec<Entry<'_>>> {
+    found
+        .pages
+        .iter()
+        .map(|page| {
+            let html = read(page)?;
+            Ok(Entry {
+                dictionary: &page.dictionary,
+                path: &page.path,
+                headword: &page.headword,
+                text: html_to_text(&html),
+                html,
+            })
+        })
+        .collect()
+}
+
+fn markdown(found: &Found) -> Result<String> {
+    let mut s = format!("# {}\n\n", found.word);
+    if let Some(note) = &found.note {
+        s.push_str(&format!("> {note}\n\n"));
+    }
+    for page in &found.pages {
+        s.push_str(&format!("## {}\n\n", title(found, page)));
+        s.push_str(&html_to_text(&read(page)?));
+        s.push('\n');
+    }
+    Ok(s)
+}
+
+/// one document, the body of each result in a section
+fn html(found: &Found) -> Result<String> {
+    let body = Selector::parse("body").unwrap();
+    let word = escape_html(&found.word);
+    let mut s = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="UTF-8">
+  <title>{word}</title>
+</head>
+<body>
+<h1>{word}</h1>
+"#
+    );
+    if let Some(note) = &found.note {
+        s.push_str(&format!("<p class=\"note\">{}</p>\n", escape_html(note)));
+    }
+    for page in &found.pages {
+        let dom = Html::parse_document(&read(page)?);
+        let inner = dom
+            .select(&body)
+            .next()
+            .map(|b| b.inner_html())
+            .unwrap_or_default();
+        s.push_str(&format!(
+            "<section>\n<h2>{}</h2>\n{inner}\n</section>\n",
+            escape_html(&title(found, page))
+        ));
+    }
+    s.push_str("</body>\n</html>\n");
+    Ok(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = "/tmp/a dir/index.html";
+
+    fn browser(name: &str) -> Option<String> {
+        (name == "BROWSER").then(|| "firefox --new-window:chromium".to_owned())
+    }
+
+    fn run(viewer: &str, target: &str) -> Vec<String> {
+        command(viewer, target, browser).unwrap()
+    }
+
+    #[test]
+    fn appended() {
+        // a path with spaces stays one argument
+        assert_eq!(run("carbonyl", PAGE), ["carbonyl", PAGE]);
+        assert_eq!(run("  xdg-open  ", PAGE), ["xdg-open", PAGE]);
+    }
+
+    #[test]
+    fn templates() {
+        assert_eq!(
+            run("w3m -T text/html {path}", PAGE),
+            ["w3m", "-T", "text/html", PAGE]
+        );
+        assert_eq!(
+            run("firefox --url={url}", PAGE),
+            ["firefox", "--url=file:///tmp/a%20dir/index.html"]
+        );
+        assert_eq!(
+            run("surf %s", "http://127.0.0.1:8080/a b"),
+            ["surf", "http://127.0.0.1:8080/a b"]
+        );
+    }
+
+    #[test]
+    fn variables() {
+        assert_eq!(run("$BROWSER", PAGE), ["firefox", "--new-window", PAGE]);
+        let e = command("$BROWSER {url}", PAGE, |_| None).unwrap_err();
+        assert_eq!(e.to_string(), "BROWSER is not set");
+        assert!(command(" ", PAGE, browser).is_err());
+    }
+
+    #[test]
+    fn blocking() {
+        assert!(is_blocking("carbonyl", browser));
+        assert!(is_blocking("/usr/bin/w3m -T text/html {path}", browser));
+        assert!(is_blocking("$BROWSER", |_| Some(
+            "lynx -force_html:firefox".to_owned()
+        )));
+        assert!(!is_blocking("$BROWSER", browser));
+        assert!(!is_blocking("$BROWSER", |_| None));
+        assert!(!is_blocking("xdg-open", browser));
+        assert!(!is_blocking("none", browser));
+        assert!(!is_blocking("", browser));
+    }
+}
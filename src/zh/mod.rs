@@ -0,0 +1,150 @@
+//! Traditional ↔ simplified Chinese conversion, character by character.
+//!
+//! The tables are bundled, `t2s.txt` maps a traditional character to its simplified form
+//! and `s2t.txt` a simplified one to its usual traditional form. A simplified character
+//! can stand for several traditional ones (发 is 發 or 髮), those are tried too.
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+const T2S: &str = include_str!("t2s.txt");
+const S2T: &str = include_str!("s2t.txt");
+
+/// at most this many forms of a word are tried
+const LIMIT: usize = 8;
+
+struct Tables {
+    t2s: HashMap<char, char>,
+    /// the usual traditional character first
+    s2t: HashMap<char, Vec<char>>,
+}
+
+/// a line is a character and its converted form, `#` starts a comment
+fn pairs(table: &str) -> impl Iterator<Item = (char, char)> + '_ {
+    table
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut chars = line.chars();
+            Some((chars.next()?, chars.next()?))
+        })
+}
+
+fn tables() -> &'static Tables {
+    static TABLES: OnceLock<Tables> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        let t2s: HashMap<char, char> = pairs(T2S).collect();
+        let mut s2t: HashMap<char, Vec<char>> = HashMap::new();
+        for (s, t) in pairs(S2T) {
+            s2t.entry(s).or_default().push(t);
+        }
+        for (&t, &s) in &t2s {
+            // not in s2t, it's a traditional character too, e.g. 家 of 傢
+            let v = s2t.entry(s).or_insert_with(|| vec![s]);
+            if !v.contains(&t) {
+                v.push(t);
+            }
+        }
+        // the rest in a stable order
+        for v in s2t.values_mut() {
+            v[1..].sort();
+        }
+        Tables { t2s, s2t }
+    })
+}
+
+pub fn is_han(c: char) -> bool {
+    matches!(c, '\u{3400}'..='\u{4dbf}' | '\u{4e00}'..='\u{9fff}' | '\u{f900}'..='\u{faff}')
+}
+
+fn to_simplified(s: &str) -> String {
+    let t2s = &tables().t2s;
+    s.chars().map(|c| *t2s.get(&c).unwrap_or(&c)).collect()
+}
+
+/// `s` in traditional characters, the usual one where there are several
+fn to_traditional(s: &str) -> String {
+    let s2t = &tables().s2t;
+    s.chars()
+        .map(|c| s2t.get(&c).and_then(|v| v.first()).copied().unwrap_or(c))
+        .collect()
+}
+
+/// `word` in the other script, most likely first, `word` itself excluded
+pub fn variants(word: &str) -> Vec<String> {
+    if !word.chars().any(is_han) {
+        return Vec::new();
+    }
+    let s2t = &tables().s2t;
+    let simplified = to_simplified(word);
+    let mut v = vec![simplified.clone(), to_traditional(word)];
+
+    // every traditional form of the simplified word, the usual characters first
+    let mut forms = vec![String::new()];
+    for c in simplified.chars() {
+        let choices = s2t.get(&c).cloned().unwrap_or_else(|| vec![c]);
+        forms = forms
+            .iter()
+            .flat_map(|f| choices.iter().map(move |c| format!("{f}{c}")))
+            .take(LIMIT * 4)
+            .collect();
+    }
+    v.extend(forms);
+
+    let mut seen = vec![word.to_owned()];
+    v.retain(|w| {
+        if seen.contains(w) {
+            false
+        } else {
+            seen.push(w.clone());
+            true
+        }
+    });
+    v.truncate(LIMIT);
+    v
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn characters() {
+        assert_eq!(to_simplified("國家"), "国家");
+        assert_eq!(to_traditional("国家"), "國家");
+        // both traditional forms are simplified the same
+        assert_eq!(to_simplified("頭髮"), "头发");
+        assert_eq!(to_simplified("發展"), "发展");
+        // the usual one first
+        assert_eq!(tables().s2t[&'发'][0], '發');
+        assert!(tables().s2t[&'发'].contains(&'髮'));
+        assert_eq!(to_traditional("abc 1"), "abc 1");
+        assert!(is_han('国'));
+        assert!(!is_han('の'));
+    }
+
+    #[test]
+    fn words() {
+        assert_eq!(variants("國家")[0], "国家");
+        assert_eq!(variants("国家")[0], "國家");
+        // one simplified character for several traditional ones
+        let v = variants("头发");
+        assert_eq!(v[0], "頭發");
+        assert!(v.contains(&"頭髮".to_owned()));
+        let v = variants("干面");
+        assert!(v.contains(&"乾麵".to_owned()));
+        assert!(v.contains(&"幹麵".to_owned()));
+        // mixed scripts
+        assert!(variants("頭发").contains(&"头发".to_owned()));
+        assert!(variants("頭发").contains(&"頭髮".to_owned()));
+    }
+
+    #[test]
+    fn other_words() {
+        assert!(variants("hello").is_empty());
+        assert!(variants("すし").is_empty());
+        // only the script differs, the other characters are kept
+        assert_eq!(variants("ok国"), ["ok國"]);
+        // not every combination is tried
+        assert!(variants("发发发发发发").len() <= LIMIT);
+    }
+}
@@ -0,0 +1,744 @@
+//! Look up articles from ZIM (Kiwix) archives.
+//! <https://wiki.openzim.org/wiki/ZIM_file_format>
+use crate::cache;
+use crate::normalize::normalize;
+use crate::utils::create_sub_dir;
+use crate::utils::groom_name;
+use crate::utils::safe_relative_path;
+use crate::view;
+use crate::T;
+use anyhow::{anyhow, Context, Result};
+use ego_tree::NodeRef;
+use fst::Map;
+use log::*;
+use memmap2::Mmap;
+use scraper::{Html, Node};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{prelude::*, BufReader, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+const MAGIC_NUMBER: u32 = 72173914;
+
+const MIME_REDIRECT: u16 = 0xffff;
+const MIME_LINK_TARGET: u16 = 0xfffe;
+const MIME_DELETED: u16 = 0xfffd;
+
+/// articles live in `C` since ZIM 6.1, in `A` before
+const ARTICLE_NAMESPACES: [u8; 2] = [b'C', b'A'];
+
+/// The zim archive to be looked up.
+pub struct Zim {
+    path: PathBuf,
+    name: String,
+    header: Header,
+    mime_types: Vec<String>,
+    /// normalized title -> entry, opened on the first miss
+    normalized: OnceLock<Option<Map<Mmap>>>,
+}
+
+#[derive(Debug)]
+struct Header {
+    entry_count: u32,
+    cluster_count: u32,
+    url_ptr_pos: u64,
+    title_ptr_pos: u64,
+    cluster_ptr_pos: u64,
+    checksum_pos: u64,
+}
+
+/// A decompressed cluster.
+struct Cluster {
+    /// extended clusters use 64 bits blob offsets
+    extended: bool,
+    data: Vec<u8>,
+}
+
+impl Cluster {
+    fn blob(&self, n: u32) -> Option<&[u8]> {
+        let offset = |i: usize| -> Option<usize> {
+            Some(if self.extended {
+                u64_at(self.data.get(i * 8..(i + 1) * 8)?, 0) as usize
+            } else {
+                u32_at(self.data.get(i * 4..(i + 1) * 4)?, 0) as usize
+            })
+        };
+        let start = offset(n as usize)?;
+        let end = offset(n as usize + 1)?;
+        self.data.get(start..end)
+    }
+}
+
+#[derive(Debug)]
+enum Target {
+    Blob { cluster: u32, blob: u32 },
+    Redirect(u32),
+    None,
+}
+
+/// A directory entry of the zim archive.
+#[derive(Debug)]
+struct Dirent {
+    mime_type: u16,
+    namespace: u8,
+    url: String,
+    title: String,
+    target: Target,
+}
+
+impl Zim {
+    pub fn new(path: &Path) -> Result<Zim> {
+        let mut f = BufReader::new(
+            File::open(path).with_context(|| format!("Failed to open zim file {:?}", path))?,
+        );
+
+        let mut buf = [0; 80];
+        f.read_exact(&mut buf)
+            .with_context(|| format!("Failed to read zim header {:?}", path))?;
+        if u32_at(&buf, 0) != MAGIC_NUMBER {
+            return Err(anyhow!("{:?} is not a zim file", path));
+        }
+        let header = Header {
+            entry_count: u32_at(&buf, 24),
+            cluster_count: u32_at(&buf, 28),
+            url_ptr_pos: u64_at(&buf, 32),
+            title_ptr_pos: u64_at(&buf, 40),
+            cluster_ptr_pos: u64_at(&buf, 48),
+            checksum_pos: u64_at(&buf, 72),
+        };
+
+        f.seek(SeekFrom::Start(u64_at(&buf, 56)))?;
+        let mut mime_types = Vec::new();
+        loop {
+            let s = read_cstr(&mut f)?;
+            if s.is_empty() {
+                break;
+            }
+            mime_types.push(s);
+        }
+
+        Ok(Zim {
+            path: path.to_path_buf(),
+            name: path.file_name().unwrap().to_str().unwrap().to_owned(),
+            header,
+            mime_types,
+            normalized: OnceLock::new(),
+        })
+    }
+
+    /// the title in the metadata, the file name if there's none
+    pub fn title(&self) -> Result<String> {
+        let mut f = self.open()?;
+        Ok(match self.metadata(&mut f, "Title") {
+            Some(title) if !title.is_empty() => title,
+            _ => self.path.file_name().unwrap().to_str().unwrap().to_owned(),
+        })
+    }
+
+    /// shown instead of the file name, see `title`
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
+    fn open(&self) -> Result<BufReader<File>> {
+        Ok(BufReader::new(File::open(&self.path).with_context(
+            || format!("Failed to open zim file {:?}", self.path),
+        )?))
+    }
+
+    fn metadata(&self, f: &mut BufReader<File>, key: &str) -> Option<String> {
+        let idx = self.find_by_url(f, b'M', key).ok()??;
+        let (_, content) = self.content(f, idx, &mut HashMap::new()).ok()?;
+        String::from_utf8(content).ok()
+    }
+
+    fn dirent(&self, f: &mut BufReader<File>, idx: u32) -> Result<Dirent> {
+        f.seek(SeekFrom::Start(self.header.url_ptr_pos + 8 * idx as u64))?;
+        let mut b = [0; 8];
+        f.read_exact(&mut b)?;
+        f.seek(SeekFrom::Start(u64::from_le_bytes(b)))?;
+
+        let mut b = [0; 8];
+        f.read_exact(&mut b)?;
+        let mime_type = u16::from_le_bytes([b[0], b[1]]);
+        let parameter_len = b[2] as u64;
+        let namespace = b[3];
+
+        let target = match mime_type {
+            MIME_REDIRECT => {
+                let mut b = [0; 4];
+                f.read_exact(&mut b)?;
+                Target::Redirect(u32::from_le_bytes(b))
+            }
+            MIME_LINK_TARGET | MIME_DELETED => Target::None,
+            _ => {
+                let mut b = [0; 8];
+                f.read_exact(&mut b)?;
+                Target::Blob {
+                    cluster: u32_at(&b, 0),
+                    blob: u32_at(&b, 4),
+                }
+            }
+        };
+
+        let url = read_cstr(f)?;
+        let mut title = read_cstr(f)?;
+        if title.is_empty() {
+            title.clone_from(&url);
+        }
+        f.seek_relative(parameter_len as i64)?;
+
+        Ok(Dirent {
+            mime_type,
+            namespace,
+            url,
+            title,
+            target,
+        })
+    }
+
+    /// binary search in the url pointer list
+    fn find_by_url(
+        &self,
+        f: &mut BufReader<File>,
+        namespace: u8,
+        url: &str,
+    ) -> Result<Option<u32>> {
+        self.binary_search(
+            f,
+            |zim, f, i| {
+                let d = zim.dirent(f, i)?;
+                Ok((i, (d.namespace, d.url)))
+            },
+            (namespace, url),
+        )
+    }
+
+    /// binary search in the title pointer list
+    fn find_by_title(
+        &self,
+        f: &mut BufReader<File>,
+        namespace: u8,
+        title: &str,
+    ) -> Result<Option<u32>> {
+        self.binary_search(
+            f,
+            |zim, f, i| {
+                f.seek(SeekFrom::Start(zim.header.title_ptr_pos + 4 * i as u64))?;
+                let mut b = [0; 4];
+                f.read_exact(&mut b)?;
+                let idx = u32::from_le_bytes(b);
+                let d = zim.dirent(f, idx)?;
+                Ok((idx, (d.namespace, d.title)))
+            },
+            (namespace, title),
+        )
+    }
+
+    fn binary_search<F>(
+        &self,
+        f: &mut BufReader<File>,
+        probe: F,
+        target: (u8, &str),
+    ) -> Result<Option<u32>>
+    where
+        F: Fn(&Self, &mut BufReader<File>, u32) -> Result<(u32, (u8, String))>,
+    {
+        let (mut lo, mut hi) = (0, self.header.entry_count);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let (idx, (namespace, key)) = probe(self, f, mid)?;
+            match (namespace, key.as_str()).cmp(&target) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => return Ok(Some(idx)),
+            }
+        }
+        Ok(None)
+    }
+
+    /// follow redirects and return the dirent and the blob of entry `idx`
+    fn content(
+        &self,
+        f: &mut BufReader<File>,
+        mut idx: u32,
+        clusters: &mut HashMap<u32, Cluster>,
+    ) -> Result<(Dirent, Vec<u8>)> {
+        // redirect loops are broken zim files, don't hang on them
+        for _ in 0..8 {
+            let dirent = self.dirent(f, idx)?;
+            match dirent.target {
+                Target::Redirect(i) => idx = i,
+                Target::None => return Err(anyhow!("{} has no content", dirent.url)),
+                Target::Blob { cluster, blob } => {
+                    if let std::collections::hash_map::Entry::Vacant(e) = clusters.entry(cluster) {
+                        e.insert(self.cluster(f, cluster)?);
+                    }
+                    let blob = clusters[&cluster]
+                        .blob(blob)
+                        .with_context(|| format!("broken cluster {cluster} in {:?}", self.path))?;
+                    return Ok((dirent, blob.to_vec()));
+                }
+            }
+        }
+        Err(anyhow!("too many redirects in {:?}", self.path))
+    }
+
+    /// read and decompress cluster `n`
+    fn cluster(&self, f: &mut BufReader<File>, n: u32) -> Result<Cluster> {
+        if n >= self.header.cluster_count {
+            return Err(anyhow!("cluster {n} out of range"));
+        }
+        f.seek(SeekFrom::Start(self.header.cluster_ptr_pos + 8 * n as u64))?;
+        let mut b = [0; 16];
+        let start = {
+            f.read_exact(&mut b[..8])?;
+            u64_at(&b, 0)
+        };
+        let end = if n + 1 < self.header.cluster_count {
+            f.read_exact(&mut b[8..])?;
+            u64_at(&b, 8)
+        } else {
+            self.header.checksum_pos
+        };
+
+        // the pointers may be corrupt
+        let file_len = f.get_ref().metadata()?.len();
+        let len = end
+            .checked_sub(start)
+            .filter(|_| end <= file_len)
+            .with_context(|| format!("bad pointer of cluster {n}: {start}..{end}"))?;
+        f.seek(SeekFrom::Start(start))?;
+        let mut raw = vec![0; len as usize];
+        f.read_exact(&mut raw)?;
+        let Some((&info, data)) = raw.split_first() else {
+            return Err(anyhow!("empty cluster {n}"));
+        };
+
+        let mut decompressed = Vec::new();
+        match info & 0x0f {
+            0 | 1 => decompressed.extend_from_slice(data),
+            4 => lzma_rs::xz_decompress(&mut BufReader::new(data), &mut decompressed)
+                .with_context(|| format!("Failed to decompress xz cluster {n}"))?,
+            5 => {
+                ruzstd::decoding::StreamingDecoder::new(data)
+                    .map_err(|e| anyhow!("Failed to decompress zstd cluster {n}: {e}"))?
+                    .read_to_end(&mut decompressed)?;
+            }
+            c => return Err(anyhow!("unsupported cluster compression {c}")),
+        }
+        Ok(Cluster {
+            extended: info & 0x10 != 0,
+            data: decompressed,
+        })
+    }
+
+    fn is_html(&self, mime_type: u16) -> bool {
+        self.mime_types
+            .get(mime_type as usize)
+            .is_some_and(|m| m.starts_with("text/html"))
+    }
+
+    /// the html of the article of `word` and the resources it references,
+    /// links to the resources are rewritten relative to the archive root ("I/foo.png")
+    fn article(
+        &self,
+        f: &mut BufReader<File>,
+        word: &str,
+        clusters: &mut HashMap<u32, Cluster>,
+    ) -> Result<(String, Vec<String>)> {
+        let Some(idx) = self.find_article(f, word)? else {
+            return Result::Err(anyhow!("not found"));
+        };
+        let (article, content) = self.content(f, idx, clusters)?;
+        if !self.is_html(article.mime_type) {
+            return Result::Err(anyhow!("{word} is not an html article"));
+        }
+        let mut definition = String::from_utf8_lossy(&content).into_owned();
+
+        let mut links: HashSet<String> = HashSet::new();
+        let dom = Html::parse_document(&definition);
+        dfs(dom.tree.root(), &mut links);
+
+        let article_path = format!("{}/{}", article.namespace as char, article.url);
+        let mut resources = Vec::new();
+        for link in links {
+            let Some((namespace, url)) = resolve_link(&article_path, &link) else {
+                continue;
+            };
+            let local = format!("{}/{url}", namespace as char);
+            definition = definition
+                .replace(&format!("\"{link}\""), &format!("\"{local}\""))
+                .replace(&format!("'{link}'"), &format!("'{local}'"));
+            resources.push(local);
+        }
+        Ok((definition, resources))
+    }
+
+    /// `path` is relative to the archive root, e.g. "I/foo.png"
+    fn resource_in(
+        &self,
+        f: &mut BufReader<File>,
+        path: &str,
+        clusters: &mut HashMap<u32, Cluster>,
+    ) -> Result<Vec<u8>> {
+        let Some((namespace, url)) = path.trim_start_matches('/').split_once('/') else {
+            return Err(anyhow!("bad path {path}"));
+        };
+        let [namespace] = namespace.as_bytes() else {
+            return Err(anyhow!("bad path {path}"));
+        };
+        let Some(idx) = self.find_by_url(f, *namespace, url)? else {
+            return Err(anyhow!("{path} not found"));
+        };
+        let (_, data) = self.content(f, idx, clusters)?;
+        Ok(data)
+    }
+
+    /// find the article of `word`, trying the usual capitalizations of titles
+    fn find_article(&self, f: &mut BufReader<File>, word: &str) -> Result<Option<u32>> {
+        let mut candidates = vec![word.to_owned(), word.to_lowercase()];
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            candidates.push(first.to_uppercase().chain(chars).collect());
+        }
+        for title in candidates {
+            for namespace in ARTICLE_NAMESPACES {
+                if let Some(idx) = self.find_by_title(f, namespace, &title)? {
+                    return Ok(Some(idx));
+                }
+            }
+        }
+
+        let normalized = self.normalized.get_or_init(|| {
+            cache::normalized(&self.path, || {
+                Ok(self
+                    .titles()?
+                    .into_iter()
+                    .map(|(title, idx)| (title, idx as u64))
+                    .collect())
+            })
+            .inspect_err(|e| error!("{}: {e:?}", self.name))
+            .ok()
+        });
+        Ok(normalized
+            .as_ref()
+            .and_then(|m| m.get(normalize(word)))
+            .map(|idx| idx as u32))
+    }
+
+    /// the titles of the articles and their entries
+    fn titles(&self) -> Result<Vec<(String, u32)>> {
+        let mut f = self.open()?;
+        let mut v = Vec::new();
+        for i in 0..self.header.entry_count {
+            let d = self.dirent(&mut f, i)?;
+            if ARTICLE_NAMESPACES.contains(&d.namespace)
+                && (d.mime_type == MIME_REDIRECT || self.is_html(d.mime_type))
+            {
+                v.push((d.title, i));
+            }
+        }
+        Ok(v)
+    }
+}
+
+fn u32_at(b: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes(b[pos..pos + 4].try_into().unwrap())
+}
+
+fn u64_at(b: &[u8], pos: usize) -> u64 {
+    u64::from_le_bytes(b[pos..pos + 8].try_into().unwrap())
+}
+
+fn read_cstr(f: &mut impl BufRead) -> Result<String> {
+    let mut buf = Vec::new();
+    f.read_until(0, &mut buf)?;
+    if buf.last() == Some(&b'\0') {
+        buf.pop();
+    }
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+/// resolve `link` relative to the entry `base` ("A/Foo"), return (namespace, url)
+fn resolve_link(base: &str, link: &str) -> Option<(u8, String)> {
+    if link.is_empty() || link.starts_with('#') || link.starts_with("//") || link.contains(':') {
+        return None;
+    }
+    let link = link.split(['?', '#']).next()?;
+
+    let mut segments: Vec<&str> = if link.starts_with('/') {
+        Vec::new()
+    } else {
+        let mut v: Vec<&str> = base.split('/').collect();
+        v.pop();
+        v
+    };
+    for segment in link.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            s => segments.push(s),
+        }
+    }
+    let (namespace, url) = segments.split_first()?;
+    if namespace.len() != 1 || url.is_empty() {
+        return None;
+    }
+    Some((namespace.as_bytes()[0], url.join("/")))
+}
+
+impl T for Zim {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn definition(&self, word: &str) -> Result<String> {
+        let mut f = self.open()?;
+        let (definition, _) = self.article(&mut f, word, &mut HashMap::new())?;
+        Ok(definition)
+    }
+
+    fn resource(&self, path: &str) -> Result<Vec<u8>> {
+        let mut f = self.open()?;
+        self.resource_in(&mut f, path, &mut HashMap::new())
+    }
+
+    fn headwords(&self) -> Result<Vec<String>> {
+        Ok(self.titles()?.into_iter().map(|(title, _)| title).collect())
+    }
+
+    fn lookup(&self, word: &str, base_dir: &Path) -> Result<PathBuf> {
+        let mut f = self.open()?;
+        let mut clusters = HashMap::new();
+        let (definition, resources) = self.article(&mut f, word, &mut clusters)?;
+
+        let base_dir = create_sub_dir(base_dir, &groom_name(&self.name))?;
+
+        let resources = if view::shows_resources() {
+            resources
+        } else {
+            Vec::new()
+        };
+        for resource in resources {
+            let Some(rel) = safe_relative_path(&resource) else {
+                continue;
+            };
+            match self.resource_in(&mut f, &resource, &mut clusters) {
+                Ok(data) => {
+                    let dest = base_dir.join(rel);
+                    fs::create_dir_all(dest.parent().unwrap())?;
+                    File::create(&dest)
+                        .with_context(|| format!("fail to create {:?}", dest))?
+                        .write_all(&data)?;
+                }
+                Err(e) => {
+                    error!("{} failed to load {resource} {e}", self.name);
+                }
+            }
+        }
+
+        let index_html = base_dir.join("index.html");
+        File::create(&index_html)?.write_all(definition.as_bytes())?;
+
+        Ok(base_dir)
+    }
+}
+
+/// collect images, scripts and stylesheets referenced by the article
+fn dfs(root: NodeRef<Node>, hm: &mut HashSet<String>) {
+    if let Node::Element(e) = root.value() {
+        if let Some(v) = e.attr("src") {
+            hm.insert(v.to_owned());
+        }
+        if e.name() == "link" {
+            if let Some(v) = e.attr("href") {
+                hm.insert(v.to_owned());
+            }
+        }
+    }
+    for x in root.children() {
+        dfs(x, hm);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// an article, a redirect to it and the title, in one uncompressed cluster
+    fn fixture(dir: &Path) -> PathBuf {
+        let html = b"<p>a red fruit</p>";
+        let title = b"Test Wiki";
+        let mut cluster = vec![1];
+        for offset in [12, 12 + html.len(), 12 + html.len() + title.len()] {
+            cluster.extend((offset as u32).to_le_bytes());
+        }
+        cluster.extend(html);
+        cluster.extend(title);
+
+        let dirent = |mime: u16, namespace: u8, target: &[u8], url: &str, title: &str| {
+            let mut b = mime.to_le_bytes().to_vec();
+            b.extend([0, namespace, 0, 0, 0, 0]);
+            b.extend(target);
+            b.extend(url.as_bytes());
+            b.push(0);
+            b.extend(title.as_bytes());
+            b.push(0);
+            b
+        };
+        let blob = |n: u32| [0u32.to_le_bytes(), n.to_le_bytes()].concat();
+        // sorted by url and by title
+        let dirents = [
+            dirent(0, b'A', &blob(0), "apple", "Apple"),
+            dirent(MIME_REDIRECT, b'A', &0u32.to_le_bytes(), "pomme", ""),
+            dirent(1, b'M', &blob(1), "Title", ""),
+        ];
+
+        let mime_list = b"text/html\0text/plain\0\0";
+        let url_ptr_pos = 80 + mime_list.len();
+        let title_ptr_pos = url_ptr_pos + 8 * dirents.len();
+        let mut pos = title_ptr_pos + 4 * dirents.len();
+        let mut url_ptrs = Vec::new();
+        for d in &dirents {
+            url_ptrs.extend((pos as u64).to_le_bytes());
+            pos += d.len();
+        }
+        let cluster_ptr_pos = pos;
+        let checksum_pos = cluster_ptr_pos + 8 + cluster.len();
+
+        let mut header = vec![0; 80];
+        header[0..4].copy_from_slice(&MAGIC_NUMBER.to_le_bytes());
+        header[4..6].copy_from_slice(&6u16.to_le_bytes());
+        header[24..28].copy_from_slice(&(dirents.len() as u32).to_le_bytes());
+        header[28..32].copy_from_slice(&1u32.to_le_bytes());
+        for (at, value) in [
+            (32, url_ptr_pos),
+            (40, title_ptr_pos),
+            (48, cluster_ptr_pos),
+            (56, 80),
+            (72, checksum_pos),
+        ] {
+            header[at..at + 8].copy_from_slice(&(value as u64).to_le_bytes());
+        }
+
+        let mut zim = header;
+        zim.extend(mime_list);
+        zim.extend(url_ptrs);
+        for i in 0..dirents.len() as u32 {
+            zim.extend(i.to_le_bytes());
+        }
+        zim.extend(dirents.concat());
+        zim.extend(((cluster_ptr_pos + 8) as u64).to_le_bytes());
+        zim.extend(cluster);
+        zim.extend([0; 16]);
+
+        let path = dir.join("test.zim");
+        fs::write(&path, zim).unwrap();
+        path
+    }
+
+    #[test]
+    fn header() {
+        let dir = tempfile::tempdir().unwrap();
+        let zim = Zim::new(&fixture(dir.path())).unwrap();
+        assert_eq!(zim.header.entry_count, 3);
+        assert_eq!(zim.header.cluster_count, 1);
+        assert_eq!(zim.mime_types, ["text/html", "text/plain"]);
+        assert!(zim.is_html(0));
+        assert!(!zim.is_html(1));
+        assert_eq!(zim.title().unwrap(), "Test Wiki");
+    }
+
+    #[test]
+    fn not_a_zim() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.zim");
+        fs::write(&path, [0; 80]).unwrap();
+        assert!(Zim::new(&path).is_err());
+    }
+
+    #[test]
+    fn dirents() {
+        let dir = tempfile::tempdir().unwrap();
+        let zim = Zim::new(&fixture(dir.path())).unwrap();
+        let mut f = zim.open().unwrap();
+
+        let d = zim.dirent(&mut f, 0).unwrap();
+        assert_eq!(
+            (d.namespace, d.url.as_str(), d.title.as_str()),
+            (b'A', "apple", "Apple")
+        );
+        assert!(matches!(
+            d.target,
+            Target::Blob {
+                cluster: 0,
+                blob: 0
+            }
+        ));
+
+        // the title is the url when it's empty
+        let d = zim.dirent(&mut f, 1).unwrap();
+        assert_eq!(d.title, "pomme");
+        assert!(matches!(d.target, Target::Redirect(0)));
+    }
+
+    #[test]
+    fn find() {
+        let dir = tempfile::tempdir().unwrap();
+        let zim = Zim::new(&fixture(dir.path())).unwrap();
+        let mut f = zim.open().unwrap();
+        assert_eq!(zim.find_by_url(&mut f, b'A', "apple").unwrap(), Some(0));
+        assert_eq!(zim.find_by_url(&mut f, b'M', "Title").unwrap(), Some(2));
+        assert_eq!(zim.find_by_url(&mut f, b'A', "pear").unwrap(), None);
+        assert_eq!(zim.find_by_title(&mut f, b'A', "Apple").unwrap(), Some(0));
+        assert_eq!(zim.find_by_title(&mut f, b'A', "apple").unwrap(), None);
+    }
+
+    #[test]
+    fn redirect() {
+        let dir = tempfile::tempdir().unwrap();
+        let zim = Zim::new(&fixture(dir.path())).unwrap();
+        let mut f = zim.open().unwrap();
+        let (d, blob) = zim.content(&mut f, 1, &mut HashMap::new()).unwrap();
+        assert_eq!(d.url, "apple");
+        assert_eq!(blob, b"<p>a red fruit</p>");
+    }
+
+    #[test]
+    fn bad_cluster_pointer() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = fixture(dir.path());
+        let zim = Zim::new(&path).unwrap();
+        let mut data = fs::read(&path).unwrap();
+        let at = zim.header.cluster_ptr_pos as usize;
+        data[at..at + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        fs::write(&path, data).unwrap();
+
+        let mut f = zim.open().unwrap();
+        assert!(zim.cluster(&mut f, 0).is_err());
+        assert!(zim.cluster(&mut f, 1).is_err());
+    }
+
+    #[test]
+    fn extended_cluster() {
+        let mut data = Vec::new();
+        for offset in [16u64, 19] {
+            data.extend(offset.to_le_bytes());
+        }
+        data.extend(b"abc");
+        let cluster = Cluster {
+            extended: true,
+            data,
+        };
+        assert_eq!(cluster.blob(0), Some(&b"abc"[..]));
+        assert_eq!(cluster.blob(1), None);
+    }
+}
@@ -0,0 +1,96 @@
+//! Run the command line against a small stardict in a temp dir.
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+/// headwords sorted as stardict sorts them
+const WORDS: &[&str] = &["dicts", "serve", "serve-dict"];
+
+/// a config whose dictionaries, caches and history are all under `dir`
+fn setup(dir: &Path) {
+    let dicts = dir.join("dicts");
+    fs::create_dir_all(&dicts).unwrap();
+    let mut idx = Vec::new();
+    let mut dict = Vec::new();
+    for word in WORDS {
+        let definition = format!("<p>definition of {word}</p>");
+        idx.extend(word.as_bytes());
+        idx.push(0);
+        idx.extend((dict.len() as u32).to_be_bytes());
+        idx.extend((definition.len() as u32).to_be_bytes());
+        dict.extend(definition.as_bytes());
+    }
+    let ifo = format!(
+        "StarDict's dict ifo file\nversion=2.4.2\nwordcount={}\nidxfilesize={}\nbookname=Test\nsametypesequence=h\n",
+        WORDS.len(),
+        idx.len()
+    );
+    fs::write(dicts.join("test.ifo"), ifo).unwrap();
+    fs::write(dicts.join("test.idx"), idx).unwrap();
+    fs::write(dicts.join("test.dict"), dict).unwrap();
+
+    let config = format!(
+        "dictionary_dirs = [{dicts:?}]\ncache_dir = {:?}\nlog_dir = {:?}\ndb_path = {:?}\nviewer = \"none\"\n",
+        dir.join("cache"),
+        dir.join("log"),
+        dir.join("history.db"),
+    );
+    fs::write(dir.join("config.toml"), config).unwrap();
+}
+
+fn run(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mdict-cli-rs"))
+        .arg("--config")
+        .arg(dir.join("config.toml"))
+        .args(args)
+        .env_remove("MDICT_CLI_NORMALIZE")
+        .env_remove("MDICT_CLI_VIEWER")
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+/// words which are also commands are looked up
+#[test]
+fn command_names_are_words() {
+    let dir = tempfile::tempdir().unwrap();
+    setup(dir.path());
+    for word in WORDS {
+        let out = run(dir.path(), &["--format", "json", word]);
+        assert!(out.status.success(), "{word}: {out:?}");
+        let json: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+        assert_eq!(json[0]["headword"], *word);
+        assert!(json[0]["text"]
+            .as_str()
+            .unwrap()
+            .contains(&format!("definition of {word}")));
+    }
+}
+
+/// a miss is an error even when a suggestion is offered and none is picked
+#[test]
+fn declined_suggestion() {
+    let dir = tempfile::tempdir().unwrap();
+    setup(dir.path());
+    let out = run(dir.path(), &["--text", "serva"]);
+    assert!(
+        String::from_utf8_lossy(&out.stdout).contains("did you mean"),
+        "{out:?}"
+    );
+    assert_eq!(out.status.code(), Some(1), "{out:?}");
+}
+
+/// --help and --version don't read the config
+#[test]
+fn broken_config() {
+    let dir = tempfile::tempdir().unwrap();
+    setup(dir.path());
+    fs::write(dir.path().join("config.toml"), "viewer = [\n").unwrap();
+    for flag in ["--help", "--version"] {
+        let out = run(dir.path(), &[flag]);
+        assert!(out.status.success(), "{flag}: {out:?}");
+        assert!(!out.stdout.is_empty(), "{flag}: {out:?}");
+    }
+    let out = run(dir.path(), &["serve"]);
+    assert_eq!(out.status.code(), Some(2), "{out:?}");
+}